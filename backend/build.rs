@@ -1,23 +1,42 @@
-// use std::env;
-// use std::path::PathBuf;
-
 fn main() {
-    // println!(
-    //     "cargo:rustc-link-search={}",
-    //     env::var("SHARED_LIBRARY_DIR").unwrap()
-    // );
+    #[cfg(feature = "hdfs")]
+    hdfs();
+}
+
+/// Links against `libhdfs` and generates bindings for `headers.h`.
+///
+/// `SHARED_LIBRARY_DIR` must point to the directory containing `libhdfs.so`.
+/// If `hdfs.h` is not on the default include path, `HDFS_INCLUDE_DIR` should
+/// point to the directory containing it.
+#[cfg(feature = "hdfs")]
+fn hdfs() {
+    use std::env;
+    use std::path::PathBuf;
+
+    println!("cargo:rerun-if-changed=headers.h");
+    println!("cargo:rerun-if-env-changed=SHARED_LIBRARY_DIR");
+    println!("cargo:rerun-if-env-changed=HDFS_INCLUDE_DIR");
+
+    println!(
+        "cargo:rustc-link-search={}",
+        env::var("SHARED_LIBRARY_DIR").expect("SHARED_LIBRARY_DIR must be set to build HDFS support")
+    );
+
+    println!("cargo:rustc-link-lib=hdfs");
+
+    let mut builder = bindgen::Builder::default()
+        .header("headers.h")
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()));
 
-    // println!("cargo:rustc-link-lib=hdfs");
+    if let Ok(include_dir) = env::var("HDFS_INCLUDE_DIR") {
+        builder = builder.clang_arg(format!("-I{}", include_dir));
+    }
 
-    // let bindings = bindgen::Builder::default()
-    //     .header("headers.h")
-    //     .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
-    //     .generate()
-    //     .expect("Unable to generate bindings");
+    let bindings = builder.generate().expect("Unable to generate bindings");
 
-    // let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
 
-    // bindings
-    //     .write_to_file(out_path.join("bindings.rs"))
-    //     .expect("Couldn't write bindings!");
+    bindings
+        .write_to_file(out_path.join("bindings.rs"))
+        .expect("Couldn't write bindings!");
 }
@@ -1 +1 @@
-// #include <hdfs.h>
\ No newline at end of file
+#include <hdfs.h>
\ No newline at end of file
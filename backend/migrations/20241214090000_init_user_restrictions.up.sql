@@ -0,0 +1,195 @@
+-- Add up migration script here
+CREATE TYPE URESTRICT AS ENUM ('block', 'mute');
+
+CREATE TABLE IF NOT EXISTS user_restrictions (
+  "type" URESTRICT NOT NULL,
+  "user_id" BIGINT NOT NULL,
+  "target_id" BIGINT NOT NULL,
+  FOREIGN KEY ("user_id") REFERENCES users ("id") ON DELETE CASCADE,
+  FOREIGN KEY ("target_id") REFERENCES users ("id") ON DELETE CASCADE,
+  PRIMARY KEY ("user_id", "target_id"),
+  CHECK ("user_id" != "target_id")
+);
+
+CREATE INDEX IF NOT EXISTS user_restrictions_target_idx ON user_restrictions ("target_id");
+
+-- Whether `blocker` has blocked `blocked`.
+CREATE OR REPLACE FUNCTION is_blocked_by(blocked BIGINT, blocker BIGINT)
+RETURNS BOOLEAN LANGUAGE sql STABLE AS $$
+  SELECT EXISTS (
+    SELECT FROM user_restrictions
+    WHERE "user_id" = blocker AND "target_id" = blocked AND "type" = 'block'
+  );
+$$;
+
+-- Whether `request_uid` has either blocked or muted `author_id`.
+CREATE OR REPLACE FUNCTION is_restricted_by(request_uid BIGINT, author_id BIGINT)
+RETURNS BOOLEAN LANGUAGE sql STABLE AS $$
+  SELECT request_uid IS NOT NULL AND EXISTS (
+    SELECT FROM user_restrictions
+    WHERE "user_id" = request_uid AND "target_id" = author_id
+  );
+$$;
+
+CREATE OR REPLACE FUNCTION fetch_replies(
+  request_uid BIGINT,
+  request_pid BIGINT,
+  parent_id BIGINT,
+  parent_path LTREE,
+  current_level INT
+)
+RETURNS JSONB AS $$
+DECLARE results JSONB;
+BEGIN
+  IF current_level = 0 THEN
+    RETURN '[]'::JSONB;
+  END IF;
+  SELECT JSONB_AGG(rp) INTO results
+  FROM (
+    SELECT
+      rp.id,
+      rp.content,
+      rp.post_id,
+      u.id AS author_id,
+      u.name AS author_name,
+      u.picture AS author_picture,
+      construct_reaction_object(crt) AS reactions,
+      ucr.type AS user_reaction,
+      fetch_replies(request_uid, request_pid, rp.id, rp.path, current_level - 1) AS children
+    FROM comments rp
+    JOIN users_view u
+    ON u.id = rp.author_id
+    LEFT JOIN comment_reactions_tally crt
+    ON crt.comment_id = rp.id
+    LEFT JOIN comment_reactions ucr
+    ON ucr.comment_id = rp.id AND ucr.user_id = request_uid
+    WHERE rp.post_id = request_pid AND rp.path = parent_path || TEXT2LTREE(parent_id::TEXT)
+    AND NOT u.is_banned AND NOT is_restricted_by(request_uid, u.id)
+    ORDER BY rp.id DESC
+    LIMIT 5
+  ) rp;
+  RETURN results;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION fetch_posts(request_uid BIGINT)
+RETURNS TABLE (
+  id BIGINT,
+  title TEXT,
+  content TEXT,
+  book_id BIGINT,
+  author_name TEXT,
+  author_picture JSONB,
+  book_reaction BREACT,
+  reactions JSONB,
+  user_reaction PREACT
+) AS $$
+  SELECT
+    rv.id,
+    rv.title,
+    rv.content,
+    rv.book_id,
+    rvu.name AS author_name,
+    rvu.picture AS author_picture,
+    rv.reaction AS book_reaction,
+    construct_reaction_object(prt) AS reactions,
+    upr.type AS user_reaction
+  FROM posts rv
+  JOIN users_view rvu
+  ON rv.author_id = rvu.id
+  LEFT JOIN post_reactions_tally prt
+  ON prt.post_id = rv.id
+  LEFT JOIN post_reactions upr
+  ON upr.post_id = rv.id AND upr.user_id = request_uid
+  WHERE NOT rvu.is_banned AND NOT is_restricted_by(request_uid, rvu.id);
+$$
+LANGUAGE sql;
+
+CREATE OR REPLACE FUNCTION fetch_comments (
+  request_uid BIGINT,
+  replies_depth INT
+)
+RETURNS TABLE (
+  id BIGINT,
+  content TEXT,
+  post_id BIGINT,
+  path LTREE,
+  author_id BIGINT,
+  author_name TEXT,
+  author_picture JSONB,
+  reactions JSONB,
+  user_reaction PREACT,
+  children JSONB
+) AS $$
+  SELECT
+    c.id,
+    c.content,
+    c.post_id,
+    c.path,
+    u.id AS author_id,
+    u.name AS author_name,
+    u.picture AS author_picture,
+    construct_reaction_object(crt) AS reactions,
+    ucr.type AS user_reaction,
+    fetch_replies(
+      request_uid => request_uid,
+      request_pid => c.post_id,
+      parent_id => c.id,
+      parent_path => c.path,
+      current_level => replies_depth
+    ) AS children
+  FROM comments c
+  JOIN users_view u
+  ON c.author_id = u.id
+  LEFT JOIN comment_reactions_tally crt
+  ON crt.comment_id = c.id
+  LEFT JOIN comment_reactions ucr
+  ON ucr.comment_id = c.id AND ucr.user_id = request_uid
+  WHERE NOT u.is_banned AND NOT is_restricted_by(request_uid, u.id)
+$$
+LANGUAGE sql;
+
+-- Users that have been blocked by the author of a post or a comment
+-- cannot react to it. 'BL001' is matched against in the backend.
+CREATE OR REPLACE FUNCTION create_post_reaction(
+  rtype PREACT,
+  usid BIGINT,
+  pid BIGINT
+)
+RETURNS VOID AS $$
+BEGIN
+  IF is_blocked_by(usid, (SELECT "author_id" FROM posts WHERE "id" = pid)) THEN
+    RAISE EXCEPTION 'user % has been blocked by the author of post %', usid, pid
+    USING ERRCODE = 'BL001';
+  END IF;
+  PERFORM FROM post_reactions WHERE "user_id" = usid AND "post_id" = pid;
+  IF FOUND THEN
+    UPDATE post_reactions SET "type" = rtype WHERE "user_id" = usid AND "post_id" = pid;
+  ELSE
+    INSERT INTO post_reactions ("type", "user_id", "post_id") VALUES (rtype, usid, pid);        
+  END IF;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION create_comment_reaction(
+  rtype PREACT,
+  usid BIGINT,
+  cid BIGINT
+)
+RETURNS VOID AS $$
+BEGIN
+  IF is_blocked_by(usid, (SELECT "author_id" FROM comments WHERE "id" = cid)) THEN
+    RAISE EXCEPTION 'user % has been blocked by the author of comment %', usid, cid
+    USING ERRCODE = 'BL001';
+  END IF;
+  PERFORM FROM comment_reactions WHERE "user_id" = usid AND "comment_id" = cid;
+  IF FOUND THEN
+    UPDATE comment_reactions SET "type" = rtype WHERE "user_id" = usid AND "comment_id" = cid;
+  ELSE
+    INSERT INTO comment_reactions ("type", "user_id", "comment_id") VALUES (rtype, usid, cid);
+  END IF;
+END;
+$$
+LANGUAGE plpgsql;
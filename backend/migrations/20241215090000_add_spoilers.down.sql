@@ -0,0 +1,127 @@
+-- Add down migration script here
+DROP FUNCTION IF EXISTS fetch_posts, fetch_comments;
+
+CREATE OR REPLACE FUNCTION fetch_replies(
+  request_uid BIGINT,
+  request_pid BIGINT,
+  parent_id BIGINT,
+  parent_path LTREE,
+  current_level INT
+)
+RETURNS JSONB AS $$
+DECLARE results JSONB;
+BEGIN
+  IF current_level = 0 THEN
+    RETURN '[]'::JSONB;
+  END IF;
+  SELECT JSONB_AGG(rp) INTO results
+  FROM (
+    SELECT
+      rp.id,
+      rp.content,
+      rp.post_id,
+      u.id AS author_id,
+      u.name AS author_name,
+      u.picture AS author_picture,
+      construct_reaction_object(crt) AS reactions,
+      ucr.type AS user_reaction,
+      fetch_replies(request_uid, request_pid, rp.id, rp.path, current_level - 1) AS children
+    FROM comments rp
+    JOIN users_view u
+    ON u.id = rp.author_id
+    LEFT JOIN comment_reactions_tally crt
+    ON crt.comment_id = rp.id
+    LEFT JOIN comment_reactions ucr
+    ON ucr.comment_id = rp.id AND ucr.user_id = request_uid
+    WHERE rp.post_id = request_pid AND rp.path = parent_path || TEXT2LTREE(parent_id::TEXT)
+    AND NOT u.is_banned AND NOT is_restricted_by(request_uid, u.id)
+    ORDER BY rp.id DESC
+    LIMIT 5
+  ) rp;
+  RETURN results;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION fetch_posts(request_uid BIGINT)
+RETURNS TABLE (
+  id BIGINT,
+  title TEXT,
+  content TEXT,
+  book_id BIGINT,
+  author_name TEXT,
+  author_picture JSONB,
+  book_reaction BREACT,
+  reactions JSONB,
+  user_reaction PREACT
+) AS $$
+  SELECT
+    rv.id,
+    rv.title,
+    rv.content,
+    rv.book_id,
+    rvu.name AS author_name,
+    rvu.picture AS author_picture,
+    rv.reaction AS book_reaction,
+    construct_reaction_object(prt) AS reactions,
+    upr.type AS user_reaction
+  FROM posts rv
+  JOIN users_view rvu
+  ON rv.author_id = rvu.id
+  LEFT JOIN post_reactions_tally prt
+  ON prt.post_id = rv.id
+  LEFT JOIN post_reactions upr
+  ON upr.post_id = rv.id AND upr.user_id = request_uid
+  WHERE NOT rvu.is_banned AND NOT is_restricted_by(request_uid, rvu.id);
+$$
+LANGUAGE sql;
+
+CREATE OR REPLACE FUNCTION fetch_comments (
+  request_uid BIGINT,
+  replies_depth INT
+)
+RETURNS TABLE (
+  id BIGINT,
+  content TEXT,
+  post_id BIGINT,
+  path LTREE,
+  author_id BIGINT,
+  author_name TEXT,
+  author_picture JSONB,
+  reactions JSONB,
+  user_reaction PREACT,
+  children JSONB
+) AS $$
+  SELECT
+    c.id,
+    c.content,
+    c.post_id,
+    c.path,
+    u.id AS author_id,
+    u.name AS author_name,
+    u.picture AS author_picture,
+    construct_reaction_object(crt) AS reactions,
+    ucr.type AS user_reaction,
+    fetch_replies(
+      request_uid => request_uid,
+      request_pid => c.post_id,
+      parent_id => c.id,
+      parent_path => c.path,
+      current_level => replies_depth
+    ) AS children
+  FROM comments c
+  JOIN users_view u
+  ON c.author_id = u.id
+  LEFT JOIN comment_reactions_tally crt
+  ON crt.comment_id = c.id
+  LEFT JOIN comment_reactions ucr
+  ON ucr.comment_id = c.id AND ucr.user_id = request_uid
+  WHERE NOT u.is_banned AND NOT is_restricted_by(request_uid, u.id)
+$$
+LANGUAGE sql;
+
+DROP FUNCTION IF EXISTS reveals_spoilers, parse_spoilers;
+
+ALTER TABLE users DROP COLUMN IF EXISTS "reveal_spoilers";
+
+ALTER TABLE posts DROP COLUMN IF EXISTS "spoiler";
@@ -0,0 +1,190 @@
+-- Add up migration script here
+ALTER TABLE posts ADD COLUMN "spoiler" BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE users ADD COLUMN "reveal_spoilers" BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Splits `content` into text and spoiler spans. Spoilers are
+-- wrapped in double pipes, e.g. "The butler ||did it||."
+-- Unclosed and empty spoilers are kept as plain text.
+CREATE OR REPLACE FUNCTION parse_spoilers(content TEXT)
+RETURNS JSONB LANGUAGE plpgsql IMMUTABLE AS $$
+DECLARE
+  parts TEXT[] := string_to_array(content, '||');
+  parts_len INT := coalesce(array_length(parts, 1), 0);
+  spans JSONB := '[]'::JSONB;
+  buffer TEXT := '';
+BEGIN
+  IF content IS NULL THEN
+    RETURN NULL;
+  END IF;
+  FOR i IN 1..parts_len LOOP
+    IF i % 2 = 1 THEN
+      buffer := buffer || parts[i];
+    ELSIF i = parts_len THEN
+      buffer := buffer || '||' || parts[i];
+    ELSIF parts[i] = '' THEN
+      buffer := buffer || '||||';
+    ELSE
+      IF buffer != '' THEN
+        spans := spans || jsonb_build_object('type', 'text', 'text', buffer);
+        buffer := '';
+      END IF;
+      spans := spans || jsonb_build_object('type', 'spoiler', 'text', parts[i]);
+    END IF;
+  END LOOP;
+  IF buffer != '' THEN
+    spans := spans || jsonb_build_object('type', 'text', 'text', buffer);
+  END IF;
+  RETURN spans;
+END;
+$$;
+
+-- Whether spoilers about `request_bid` can be shown to `request_uid` as-is:
+-- they must have asked for it and have finished reading the book.
+CREATE OR REPLACE FUNCTION reveals_spoilers(request_uid BIGINT, request_bid BIGINT)
+RETURNS BOOLEAN LANGUAGE sql STABLE AS $$
+  SELECT request_uid IS NOT NULL AND EXISTS (
+    SELECT FROM users u
+    JOIN users_books ub ON ub.user_id = u.id
+    WHERE u.id = request_uid AND u.reveal_spoilers AND ub.book_id = request_bid AND ub.completed
+  );
+$$;
+
+DROP FUNCTION IF EXISTS fetch_posts, fetch_comments;
+
+CREATE OR REPLACE FUNCTION fetch_replies(
+  request_uid BIGINT,
+  request_pid BIGINT,
+  parent_id BIGINT,
+  parent_path LTREE,
+  current_level INT
+)
+RETURNS JSONB AS $$
+DECLARE
+  results JSONB;
+  reveal BOOLEAN;
+BEGIN
+  IF current_level = 0 THEN
+    RETURN '[]'::JSONB;
+  END IF;
+  SELECT reveals_spoilers(request_uid, p.book_id) INTO reveal
+  FROM posts p WHERE p.id = request_pid;
+  SELECT JSONB_AGG(rp) INTO results
+  FROM (
+    SELECT
+      rp.id,
+      rp.content,
+      parse_spoilers(rp.content) AS content_spans,
+      reveal AS reveal_spoilers,
+      rp.post_id,
+      u.id AS author_id,
+      u.name AS author_name,
+      u.picture AS author_picture,
+      construct_reaction_object(crt) AS reactions,
+      ucr.type AS user_reaction,
+      fetch_replies(request_uid, request_pid, rp.id, rp.path, current_level - 1) AS children
+    FROM comments rp
+    JOIN users_view u
+    ON u.id = rp.author_id
+    LEFT JOIN comment_reactions_tally crt
+    ON crt.comment_id = rp.id
+    LEFT JOIN comment_reactions ucr
+    ON ucr.comment_id = rp.id AND ucr.user_id = request_uid
+    WHERE rp.post_id = request_pid AND rp.path = parent_path || TEXT2LTREE(parent_id::TEXT)
+    AND NOT u.is_banned AND NOT is_restricted_by(request_uid, u.id)
+    ORDER BY rp.id DESC
+    LIMIT 5
+  ) rp;
+  RETURN results;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION fetch_posts(request_uid BIGINT)
+RETURNS TABLE (
+  id BIGINT,
+  title TEXT,
+  content TEXT,
+  content_spans JSONB,
+  spoiler BOOLEAN,
+  reveal_spoilers BOOLEAN,
+  book_id BIGINT,
+  author_name TEXT,
+  author_picture JSONB,
+  book_reaction BREACT,
+  reactions JSONB,
+  user_reaction PREACT
+) AS $$
+  SELECT
+    rv.id,
+    rv.title,
+    rv.content,
+    parse_spoilers(rv.content) AS content_spans,
+    rv.spoiler,
+    reveals_spoilers(request_uid, rv.book_id) AS reveal_spoilers,
+    rv.book_id,
+    rvu.name AS author_name,
+    rvu.picture AS author_picture,
+    rv.reaction AS book_reaction,
+    construct_reaction_object(prt) AS reactions,
+    upr.type AS user_reaction
+  FROM posts rv
+  JOIN users_view rvu
+  ON rv.author_id = rvu.id
+  LEFT JOIN post_reactions_tally prt
+  ON prt.post_id = rv.id
+  LEFT JOIN post_reactions upr
+  ON upr.post_id = rv.id AND upr.user_id = request_uid
+  WHERE NOT rvu.is_banned AND NOT is_restricted_by(request_uid, rvu.id);
+$$
+LANGUAGE sql;
+
+CREATE OR REPLACE FUNCTION fetch_comments (
+  request_uid BIGINT,
+  replies_depth INT
+)
+RETURNS TABLE (
+  id BIGINT,
+  content TEXT,
+  content_spans JSONB,
+  reveal_spoilers BOOLEAN,
+  post_id BIGINT,
+  path LTREE,
+  author_id BIGINT,
+  author_name TEXT,
+  author_picture JSONB,
+  reactions JSONB,
+  user_reaction PREACT,
+  children JSONB
+) AS $$
+  SELECT
+    c.id,
+    c.content,
+    parse_spoilers(c.content) AS content_spans,
+    reveals_spoilers(request_uid, p.book_id) AS reveal_spoilers,
+    c.post_id,
+    c.path,
+    u.id AS author_id,
+    u.name AS author_name,
+    u.picture AS author_picture,
+    construct_reaction_object(crt) AS reactions,
+    ucr.type AS user_reaction,
+    fetch_replies(
+      request_uid => request_uid,
+      request_pid => c.post_id,
+      parent_id => c.id,
+      parent_path => c.path,
+      current_level => replies_depth
+    ) AS children
+  FROM comments c
+  JOIN posts p
+  ON p.id = c.post_id
+  JOIN users_view u
+  ON c.author_id = u.id
+  LEFT JOIN comment_reactions_tally crt
+  ON crt.comment_id = c.id
+  LEFT JOIN comment_reactions ucr
+  ON ucr.comment_id = c.id AND ucr.user_id = request_uid
+  WHERE NOT u.is_banned AND NOT is_restricted_by(request_uid, u.id)
+$$
+LANGUAGE sql;
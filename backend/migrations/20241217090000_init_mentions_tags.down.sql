@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS comment_mentions_user_idx, post_mentions_user_idx, comment_tags_tag_idx, post_tags_tag_idx;
+
+DROP TABLE IF EXISTS comment_mentions, post_mentions, comment_tags, post_tags, tags;
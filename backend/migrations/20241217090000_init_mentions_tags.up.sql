@@ -0,0 +1,45 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS tags (
+  "id" BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  "name" TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS post_tags (
+  "post_id" BIGINT NOT NULL,
+  "tag_id" BIGINT NOT NULL,
+  FOREIGN KEY ("post_id") REFERENCES posts ("id") ON DELETE CASCADE,
+  FOREIGN KEY ("tag_id") REFERENCES tags ("id") ON DELETE CASCADE,
+  PRIMARY KEY ("post_id", "tag_id")
+);
+
+CREATE TABLE IF NOT EXISTS comment_tags (
+  "comment_id" BIGINT NOT NULL,
+  "tag_id" BIGINT NOT NULL,
+  FOREIGN KEY ("comment_id") REFERENCES comments ("id") ON DELETE CASCADE,
+  FOREIGN KEY ("tag_id") REFERENCES tags ("id") ON DELETE CASCADE,
+  PRIMARY KEY ("comment_id", "tag_id")
+);
+
+CREATE TABLE IF NOT EXISTS post_mentions (
+  "post_id" BIGINT NOT NULL,
+  "user_id" BIGINT NOT NULL,
+  FOREIGN KEY ("post_id") REFERENCES posts ("id") ON DELETE CASCADE,
+  FOREIGN KEY ("user_id") REFERENCES users ("id") ON DELETE CASCADE,
+  PRIMARY KEY ("post_id", "user_id")
+);
+
+CREATE TABLE IF NOT EXISTS comment_mentions (
+  "comment_id" BIGINT NOT NULL,
+  "user_id" BIGINT NOT NULL,
+  FOREIGN KEY ("comment_id") REFERENCES comments ("id") ON DELETE CASCADE,
+  FOREIGN KEY ("user_id") REFERENCES users ("id") ON DELETE CASCADE,
+  PRIMARY KEY ("comment_id", "user_id")
+);
+
+CREATE INDEX IF NOT EXISTS post_tags_tag_idx ON post_tags ("tag_id");
+
+CREATE INDEX IF NOT EXISTS comment_tags_tag_idx ON comment_tags ("tag_id");
+
+CREATE INDEX IF NOT EXISTS post_mentions_user_idx ON post_mentions ("user_id");
+
+CREATE INDEX IF NOT EXISTS comment_mentions_user_idx ON comment_mentions ("user_id");
@@ -0,0 +1,14 @@
+-- Add down migration script here
+CREATE OR REPLACE FUNCTION construct_image(owner_id BIGINT, id BIGINT, ext TEXT)
+RETURNS JSONB LANGUAGE sql IMMUTABLE AS $$SELECT jsonb_build_object('id', id, 'ext', ext, 'owner', owner_id)$$;
+
+DELETE FROM files WHERE variant IS NOT NULL;
+
+DROP INDEX IF EXISTS files_parent_id_variant_idx;
+
+ALTER TABLE files
+DROP COLUMN IF EXISTS "variant",
+DROP COLUMN IF EXISTS "width",
+DROP COLUMN IF EXISTS "height";
+
+DROP TYPE FVARIANT;
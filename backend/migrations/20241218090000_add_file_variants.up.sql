@@ -0,0 +1,35 @@
+-- Add up migration script here
+CREATE TYPE FVARIANT AS ENUM ('thumbnail', 'medium');
+
+ALTER TABLE files
+ADD COLUMN "variant" FVARIANT DEFAULT NULL,
+ADD COLUMN "width" INT DEFAULT NULL,
+ADD COLUMN "height" INT DEFAULT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS files_parent_id_variant_idx ON files (parent_id, variant) WHERE variant IS NOT NULL;
+
+-- No longer immutable, since variants are read from `files`. Returns NULL
+-- instead of an object full of NULLs when there is no image.
+CREATE OR REPLACE FUNCTION construct_image(owner_id BIGINT, id BIGINT, ext TEXT)
+RETURNS JSONB LANGUAGE sql STABLE AS $$
+  SELECT jsonb_build_object(
+    'id', $2,
+    'ext', $3,
+    'owner', $1,
+    'width', (SELECT f.width FROM files f WHERE f.id = $2),
+    'height', (SELECT f.height FROM files f WHERE f.id = $2),
+    'variants', coalesce((
+      SELECT jsonb_agg(jsonb_build_object(
+        'id', v.id,
+        'ext', v.ext,
+        'owner', v.owner_id,
+        'variant', v.variant,
+        'width', v.width,
+        'height', v.height
+      ) ORDER BY v.width)
+      FROM files v
+      WHERE v.parent_id = $2 AND v.variant IS NOT NULL
+    ), '[]'::JSONB)
+  )
+  WHERE $2 IS NOT NULL
+$$;
@@ -0,0 +1,10 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS ensure_users_files_ready ON users;
+DROP TRIGGER IF EXISTS ensure_books_files_ready ON books;
+DROP FUNCTION IF EXISTS ensure_files_ready;
+
+DELETE FROM files WHERE status = 'pending';
+
+ALTER TABLE files DROP COLUMN IF EXISTS "status";
+
+DROP TYPE IF EXISTS FSTATUS;
@@ -0,0 +1,32 @@
+-- Add up migration script here
+CREATE TYPE FSTATUS AS ENUM ('pending', 'ready');
+
+-- Files uploaded directly to storage stay `pending` until the upload is completed.
+ALTER TABLE files ADD COLUMN "status" FSTATUS NOT NULL DEFAULT 'ready';
+
+-- Prevents rows from referencing files that have not been uploaded yet.
+-- The arguments are the names of the columns holding file ids.
+CREATE OR REPLACE FUNCTION ensure_files_ready() RETURNS trigger AS
+$trigger$
+DECLARE
+  column_name TEXT;
+  file_id BIGINT;
+BEGIN
+  FOREACH column_name IN ARRAY TG_ARGV LOOP
+    file_id := (to_jsonb(NEW) ->> column_name)::BIGINT;
+    IF EXISTS (SELECT 1 FROM files WHERE id = file_id AND status <> 'ready') THEN
+      RAISE EXCEPTION 'file % has not been uploaded yet', file_id
+      USING ERRCODE = 'foreign_key_violation', CONSTRAINT = 'files_status_ready';
+    END IF;
+  END LOOP;
+  RETURN NEW;
+END;
+$trigger$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER ensure_books_files_ready
+BEFORE INSERT OR UPDATE OF "cover_id", "spine_id"
+ON books FOR EACH ROW EXECUTE FUNCTION ensure_files_ready('cover_id', 'spine_id');
+
+CREATE OR REPLACE TRIGGER ensure_users_files_ready
+BEFORE INSERT OR UPDATE OF "picture_id"
+ON users FOR EACH ROW EXECUTE FUNCTION ensure_files_ready('picture_id');
@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS users_picture_id_idx;
+DROP INDEX IF EXISTS books_spine_id_idx;
+DROP INDEX IF EXISTS books_cover_id_idx;
+
+ALTER TABLE files
+DROP COLUMN IF EXISTS "size",
+DROP COLUMN IF EXISTS "created_at";
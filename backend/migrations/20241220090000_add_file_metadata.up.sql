@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- Files uploaded before sizes were recorded keep a NULL size.
+ALTER TABLE files
+ADD COLUMN "size" BIGINT DEFAULT NULL,
+ADD COLUMN "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+CREATE INDEX IF NOT EXISTS books_cover_id_idx ON books (cover_id);
+CREATE INDEX IF NOT EXISTS books_spine_id_idx ON books (spine_id);
+CREATE INDEX IF NOT EXISTS users_picture_id_idx ON users (picture_id);
@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS track_users_file_references ON users;
+DROP TRIGGER IF EXISTS track_books_file_references ON books;
+DROP FUNCTION IF EXISTS track_file_references;
+
+DROP INDEX IF EXISTS files_detached_at_idx;
+
+ALTER TABLE files DROP COLUMN IF EXISTS "detached_at";
@@ -0,0 +1,34 @@
+-- Add up migration script here
+-- Set when a file stops being used as a book cover/spine or a profile picture,
+-- so that replaced files can be collected once they are no longer needed.
+ALTER TABLE files ADD COLUMN "detached_at" TIMESTAMPTZ DEFAULT NULL;
+
+CREATE INDEX IF NOT EXISTS files_detached_at_idx ON files (detached_at) WHERE detached_at IS NOT NULL;
+
+-- The arguments are the names of the columns holding file ids.
+CREATE OR REPLACE FUNCTION track_file_references() RETURNS trigger AS
+$trigger$
+DECLARE
+  column_name TEXT;
+  old_id BIGINT;
+  new_id BIGINT;
+BEGIN
+  FOREACH column_name IN ARRAY TG_ARGV LOOP
+    old_id := CASE WHEN TG_OP <> 'INSERT' THEN (to_jsonb(OLD) ->> column_name)::BIGINT END;
+    new_id := CASE WHEN TG_OP <> 'DELETE' THEN (to_jsonb(NEW) ->> column_name)::BIGINT END;
+    IF old_id IS DISTINCT FROM new_id THEN
+      UPDATE files SET detached_at = NOW() WHERE id = old_id;
+      UPDATE files SET detached_at = NULL WHERE id = new_id;
+    END IF;
+  END LOOP;
+  RETURN NULL;
+END;
+$trigger$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER track_books_file_references
+AFTER INSERT OR DELETE OR UPDATE OF "cover_id", "spine_id"
+ON books FOR EACH ROW EXECUTE FUNCTION track_file_references('cover_id', 'spine_id');
+
+CREATE OR REPLACE TRIGGER track_users_file_references
+AFTER INSERT OR DELETE OR UPDATE OF "picture_id"
+ON users FOR EACH ROW EXECUTE FUNCTION track_file_references('picture_id');
@@ -0,0 +1,32 @@
+-- Add down migration script here
+CREATE OR REPLACE FUNCTION construct_image(owner_id BIGINT, id BIGINT, ext TEXT)
+RETURNS JSONB LANGUAGE sql STABLE AS $$
+  SELECT jsonb_build_object(
+    'id', $2,
+    'ext', $3,
+    'owner', $1,
+    'width', (SELECT f.width FROM files f WHERE f.id = $2),
+    'height', (SELECT f.height FROM files f WHERE f.id = $2),
+    'variants', coalesce((
+      SELECT jsonb_agg(jsonb_build_object(
+        'id', v.id,
+        'ext', v.ext,
+        'owner', v.owner_id,
+        'variant', v.variant,
+        'width', v.width,
+        'height', v.height
+      ) ORDER BY v.width)
+      FROM files v
+      WHERE v.parent_id = $2 AND v.variant IS NOT NULL
+    ), '[]'::JSONB)
+  )
+  WHERE $2 IS NOT NULL
+$$;
+
+DROP INDEX IF EXISTS files_key_idx;
+
+ALTER TABLE files
+DROP COLUMN IF EXISTS "visibility",
+DROP COLUMN IF EXISTS "key";
+
+DROP TYPE IF EXISTS FVISIBILITY;
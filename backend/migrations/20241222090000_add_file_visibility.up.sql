@@ -0,0 +1,40 @@
+-- Add up migration script here
+CREATE TYPE FVISIBILITY AS ENUM ('public', 'private');
+
+ALTER TABLE files
+ADD COLUMN "visibility" FVISIBILITY NOT NULL DEFAULT 'public',
+ADD COLUMN "key" TEXT DEFAULT NULL;
+
+-- Files stored before keys were random keep the key they were stored under.
+UPDATE files SET "key" = owner_id || '-' || id || '.' || ext;
+
+ALTER TABLE files ALTER COLUMN "key" SET NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS files_key_idx ON files ("key");
+
+CREATE OR REPLACE FUNCTION construct_image(owner_id BIGINT, id BIGINT, ext TEXT)
+RETURNS JSONB LANGUAGE sql STABLE AS $$
+  SELECT jsonb_build_object(
+    'id', $2,
+    'ext', $3,
+    'owner', $1,
+    'key', f.key,
+    'width', f.width,
+    'height', f.height,
+    'variants', coalesce((
+      SELECT jsonb_agg(jsonb_build_object(
+        'id', v.id,
+        'ext', v.ext,
+        'owner', v.owner_id,
+        'key', v.key,
+        'variant', v.variant,
+        'width', v.width,
+        'height', v.height
+      ) ORDER BY v.width)
+      FROM files v
+      WHERE v.parent_id = $2 AND v.variant IS NOT NULL
+    ), '[]'::JSONB)
+  )
+  FROM files f
+  WHERE f.id = $2
+$$;
@@ -0,0 +1,13 @@
+-- Add down migration script here
+DELETE FROM files WHERE status = 'quarantined';
+
+ALTER TABLE files DROP COLUMN IF EXISTS threat;
+
+-- Values cannot be removed from an enum, so the type is recreated.
+ALTER TYPE FSTATUS RENAME TO FSTATUS_OLD;
+CREATE TYPE FSTATUS AS ENUM ('pending', 'ready');
+ALTER TABLE files
+  ALTER COLUMN "status" DROP DEFAULT,
+  ALTER COLUMN "status" TYPE FSTATUS USING "status"::TEXT::FSTATUS,
+  ALTER COLUMN "status" SET DEFAULT 'ready';
+DROP TYPE FSTATUS_OLD;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- Files flagged by the malware scanner are kept aside and never served.
+ALTER TYPE FSTATUS ADD VALUE IF NOT EXISTS 'quarantined';
+
+-- The name of the threat the scanner found.
+ALTER TABLE files ADD COLUMN IF NOT EXISTS threat TEXT;
@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS book_categories_name_trgm_idx;
+DROP INDEX IF EXISTS book_authors_name_trgm_idx;
+DROP INDEX IF EXISTS books_title_trgm_idx;
+
+DROP EXTENSION IF EXISTS pg_trgm;
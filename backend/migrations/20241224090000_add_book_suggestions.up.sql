@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Suggestions match lowercased names by substring and by trigram similarity,
+-- both of which these indexes serve.
+CREATE INDEX IF NOT EXISTS books_title_trgm_idx ON books USING GIN (lower("title") gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS book_authors_name_trgm_idx ON book_authors USING GIN (lower("name") gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS book_categories_name_trgm_idx ON book_categories USING GIN (lower("name") gin_trgm_ops);
@@ -0,0 +1,12 @@
+-- Add down migration script here
+DROP FUNCTION IF EXISTS search_headline;
+
+DROP INDEX IF EXISTS users_text_search_idx;
+DROP INDEX IF EXISTS comments_text_search_idx;
+DROP INDEX IF EXISTS posts_text_search_idx;
+
+ALTER TABLE users DROP COLUMN IF EXISTS "text_search";
+ALTER TABLE comments DROP COLUMN IF EXISTS "text_search";
+ALTER TABLE posts DROP COLUMN IF EXISTS "text_search";
+
+DROP FUNCTION IF EXISTS strip_spoilers;
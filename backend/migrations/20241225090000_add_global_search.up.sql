@@ -0,0 +1,42 @@
+-- Add up migration script here
+-- The text of `content` outside of spoilers, so that searching cannot reveal them.
+CREATE OR REPLACE FUNCTION strip_spoilers(content TEXT)
+RETURNS TEXT LANGUAGE sql IMMUTABLE AS $$
+  SELECT string_agg(span ->> 'text', ' ')
+  FROM jsonb_array_elements(parse_spoilers(content)) span
+  WHERE span ->> 'type' = 'text';
+$$;
+
+-- The content of posts marked as spoilers is left out entirely.
+ALTER TABLE posts ADD COLUMN "text_search" TSVECTOR GENERATED ALWAYS AS (
+  setweight(to_tsvector('english', "title"), 'A') ||
+  setweight(to_tsvector('english', CASE
+    WHEN "spoiler" THEN ''
+    ELSE coalesce(strip_spoilers("content"), '')
+  END), 'B')
+) STORED;
+
+ALTER TABLE comments ADD COLUMN "text_search" TSVECTOR GENERATED ALWAYS AS (
+  to_tsvector('english', coalesce(strip_spoilers("content"), ''))
+) STORED;
+
+-- Names are not natural language, so they are neither stemmed nor filtered.
+ALTER TABLE users ADD COLUMN "text_search" TSVECTOR GENERATED ALWAYS AS (
+  to_tsvector('simple', "name")
+) STORED;
+
+CREATE INDEX IF NOT EXISTS posts_text_search_idx ON posts USING GIN ("text_search");
+CREATE INDEX IF NOT EXISTS comments_text_search_idx ON comments USING GIN ("text_search");
+CREATE INDEX IF NOT EXISTS users_text_search_idx ON users USING GIN ("text_search");
+
+-- Highlights the parts of `content` matching `query`. The content is escaped
+-- first, so that the result can be rendered as HTML.
+CREATE OR REPLACE FUNCTION search_headline(config REGCONFIG, content TEXT, query TSQUERY)
+RETURNS TEXT LANGUAGE sql IMMUTABLE AS $$
+  SELECT ts_headline(
+    config,
+    replace(replace(replace(content, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'),
+    query,
+    'StartSel=<mark>, StopSel=</mark>, MinWords=10, MaxWords=30, MaxFragments=2'
+  );
+$$;
@@ -0,0 +1,43 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS after_update_book_languages ON book_languages;
+DROP FUNCTION IF EXISTS after_update_book_languages;
+DROP TRIGGER IF EXISTS before_insert_book_languages ON book_languages;
+DROP FUNCTION IF EXISTS before_insert_book_languages;
+
+CREATE OR REPLACE FUNCTION after_update_books()
+RETURNS trigger AS $trigger$
+DECLARE
+  authors TEXT;
+  categories TEXT;
+BEGIN
+  SELECT immut_array_to_string(array_agg(ba."name")) INTO authors
+  FROM book_authors ba
+  JOIN book_to_author bta ON bta.author_id = ba.id
+  WHERE bta.book_id = NEW.id
+  GROUP BY bta.book_id;
+  SELECT immut_array_to_string(array_agg(bc."name")) INTO categories
+  FROM book_categories bc
+  JOIN book_to_category btc ON btc.category_id = bc.id
+  WHERE btc.book_id = NEW.id
+  GROUP BY btc.book_id;
+  UPDATE books SET text_search = (
+    setweight(to_tsvector(coalesce("title", '')), 'A') || ' ' ||
+    setweight(to_tsvector(coalesce("summary", '')), 'B') || ' ' ||
+    setweight(to_tsvector(coalesce(authors, '')), 'B') || ' ' ||
+    setweight(to_tsvector(coalesce(categories, '')), 'B') || ' ' ||
+    setweight(to_tsvector(coalesce("name", '')), 'C')
+  ) WHERE id = NEW.id;
+  RETURN NEW;
+END;
+$trigger$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER after_update_books
+AFTER INSERT OR UPDATE OF "title", "summary", "name"
+ON books FOR EACH ROW EXECUTE FUNCTION after_update_books();
+
+ALTER TABLE book_languages DROP COLUMN IF EXISTS "ts_config";
+
+DROP FUNCTION IF EXISTS language_ts_config;
+
+UPDATE books SET title = title;
@@ -0,0 +1,115 @@
+-- Add up migration script here
+-- Maps an ISO 639-1 language code, optionally followed by a region, to the
+-- text search configuration of that language. Languages Postgres cannot
+-- stem are searched with `simple`.
+CREATE OR REPLACE FUNCTION language_ts_config(code TEXT)
+RETURNS REGCONFIG LANGUAGE sql STABLE AS $$
+  SELECT coalesce(
+    (
+      SELECT c.oid::REGCONFIG FROM pg_ts_config c
+      WHERE c.cfgname = CASE split_part(lower(code), '-', 1)
+        WHEN 'ar' THEN 'arabic'
+        WHEN 'hy' THEN 'armenian'
+        WHEN 'eu' THEN 'basque'
+        WHEN 'ca' THEN 'catalan'
+        WHEN 'da' THEN 'danish'
+        WHEN 'nl' THEN 'dutch'
+        WHEN 'en' THEN 'english'
+        WHEN 'fi' THEN 'finnish'
+        WHEN 'fr' THEN 'french'
+        WHEN 'de' THEN 'german'
+        WHEN 'el' THEN 'greek'
+        WHEN 'hi' THEN 'hindi'
+        WHEN 'hu' THEN 'hungarian'
+        WHEN 'id' THEN 'indonesian'
+        WHEN 'ga' THEN 'irish'
+        WHEN 'it' THEN 'italian'
+        WHEN 'lt' THEN 'lithuanian'
+        WHEN 'ne' THEN 'nepali'
+        WHEN 'no' THEN 'norwegian'
+        WHEN 'nb' THEN 'norwegian'
+        WHEN 'nn' THEN 'norwegian'
+        WHEN 'pt' THEN 'portuguese'
+        WHEN 'ro' THEN 'romanian'
+        WHEN 'ru' THEN 'russian'
+        WHEN 'sr' THEN 'serbian'
+        WHEN 'es' THEN 'spanish'
+        WHEN 'sv' THEN 'swedish'
+        WHEN 'ta' THEN 'tamil'
+        WHEN 'tr' THEN 'turkish'
+        WHEN 'yi' THEN 'yiddish'
+      END
+    ),
+    'simple'::REGCONFIG
+  );
+$$;
+
+ALTER TABLE book_languages ADD COLUMN "ts_config" REGCONFIG;
+UPDATE book_languages SET ts_config = language_ts_config(code);
+ALTER TABLE book_languages ALTER COLUMN "ts_config" SET NOT NULL;
+
+-- Languages inserted without a configuration get the one matching their code.
+CREATE OR REPLACE FUNCTION before_insert_book_languages()
+RETURNS trigger AS $trigger$
+BEGIN
+  NEW.ts_config := coalesce(NEW.ts_config, language_ts_config(NEW.code));
+  RETURN NEW;
+END;
+$trigger$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER before_insert_book_languages
+BEFORE INSERT ON book_languages
+FOR EACH ROW EXECUTE FUNCTION before_insert_book_languages();
+
+-- Books are stemmed in their own language.
+CREATE OR REPLACE FUNCTION after_update_books()
+RETURNS trigger AS $trigger$
+DECLARE
+  authors TEXT;
+  categories TEXT;
+  config REGCONFIG;
+BEGIN
+  SELECT immut_array_to_string(array_agg(ba."name")) INTO authors
+  FROM book_authors ba
+  JOIN book_to_author bta ON bta.author_id = ba.id
+  WHERE bta.book_id = NEW.id
+  GROUP BY bta.book_id;
+  SELECT immut_array_to_string(array_agg(bc."name")) INTO categories
+  FROM book_categories bc
+  JOIN book_to_category btc ON btc.category_id = bc.id
+  WHERE btc.book_id = NEW.id
+  GROUP BY btc.book_id;
+  SELECT bl.ts_config INTO config
+  FROM book_languages bl
+  WHERE bl.code = NEW.language;
+  UPDATE books SET text_search = (
+    setweight(to_tsvector(config, coalesce("title", '')), 'A') || ' ' ||
+    setweight(to_tsvector(config, coalesce("summary", '')), 'B') || ' ' ||
+    setweight(to_tsvector(config, coalesce(authors, '')), 'B') || ' ' ||
+    setweight(to_tsvector(config, coalesce(categories, '')), 'B') || ' ' ||
+    setweight(to_tsvector(config, coalesce("name", '')), 'C')
+  ) WHERE id = NEW.id;
+  RETURN NEW;
+END;
+$trigger$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER after_update_books
+AFTER INSERT OR UPDATE OF "title", "summary", "name", "language"
+ON books FOR EACH ROW EXECUTE FUNCTION after_update_books();
+
+-- Changing the configuration of a language reindexes its books.
+CREATE OR REPLACE FUNCTION after_update_book_languages()
+RETURNS trigger AS $trigger$
+BEGIN
+  UPDATE books SET language = language WHERE language = NEW.code;
+  RETURN NEW;
+END;
+$trigger$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER after_update_book_languages
+AFTER UPDATE OF "ts_config" ON book_languages
+FOR EACH ROW WHEN (OLD.ts_config IS DISTINCT FROM NEW.ts_config)
+EXECUTE FUNCTION after_update_book_languages();
+
+UPDATE books SET title = title;
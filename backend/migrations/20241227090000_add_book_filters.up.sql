@@ -0,0 +1,79 @@
+-- Add up migration script here
+-- The books matching `query`, along with whether they pass each of the other
+-- filters. Filters that are NULL let every book through. Keeping the filters
+-- apart lets facets be counted over the books passing all the others.
+CREATE OR REPLACE FUNCTION filter_books(
+  query TEXT,
+  query_lang TEXT,
+  languages TEXT[],
+  min_pages INT,
+  max_pages INT,
+  min_rating FLOAT8,
+  has_reviews BOOLEAN,
+  categories BIGINT[],
+  all_categories BOOLEAN,
+  authors BIGINT[],
+  all_authors BOOLEAN
+)
+RETURNS TABLE (
+  id BIGINT,
+  language TEXT,
+  pages INT,
+  rating FLOAT8,
+  reviewed BOOLEAN,
+  rank DECIMAL,
+  language_ok BOOLEAN,
+  pages_ok BOOLEAN,
+  rating_ok BOOLEAN,
+  reviews_ok BOOLEAN,
+  categories_ok BOOLEAN,
+  authors_ok BOOLEAN
+) AS $$
+  SELECT
+    b.id,
+    b.language,
+    b.pages,
+    r.rating,
+    r.reviewed,
+    CASE
+      WHEN q.query IS NULL THEN 0
+      ELSE ts_rank(b.text_search, q.query)
+    END + coalesce(books_boost_rating(brt), 0) AS rank,
+    languages IS NULL OR b.language = ANY(languages),
+    (min_pages IS NULL OR b.pages >= min_pages) AND (max_pages IS NULL OR b.pages <= max_pages),
+    min_rating IS NULL OR r.rating >= min_rating,
+    has_reviews IS NULL OR r.reviewed = has_reviews,
+    categories IS NULL OR CASE
+      WHEN all_categories THEN bc.ids @> categories
+      ELSE bc.ids && categories
+    END,
+    authors IS NULL OR CASE
+      WHEN all_authors THEN ba.ids @> authors
+      ELSE ba.ids && authors
+    END
+  FROM books b
+  LEFT JOIN book_reactions_tally brt ON brt.book_id = b.id
+  JOIN LATERAL (
+    SELECT
+      CASE WHEN brt.total > 0 THEN brt.like::FLOAT8 / brt.total ELSE NULL END AS rating,
+      coalesce(brt.total, 0) > 0 AS reviewed
+  ) r ON TRUE
+  JOIN LATERAL (
+    SELECT CASE
+      WHEN query IS NOT NULL THEN websearch_to_tsquery(bl.ts_config, query)
+      ELSE NULL
+    END AS query
+    FROM book_languages bl
+    WHERE bl.code = coalesce(query_lang, b.language)
+  ) q ON TRUE
+  JOIN LATERAL (
+    SELECT coalesce(array_agg(btc.category_id), '{}') AS ids
+    FROM book_to_category btc WHERE btc.book_id = b.id
+  ) bc ON TRUE
+  JOIN LATERAL (
+    SELECT coalesce(array_agg(bta.author_id), '{}') AS ids
+    FROM book_to_author bta WHERE bta.book_id = b.id
+  ) ba ON TRUE
+  WHERE q.query IS NULL OR b.text_search @@ q.query;
+$$
+LANGUAGE sql STABLE;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP FUNCTION IF EXISTS refresh_recommendations;
+DROP TABLE IF EXISTS user_recommendations;
+DROP TABLE IF EXISTS book_recommendations;
@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP MATERIALIZED VIEW IF EXISTS trending_posts;
+DROP MATERIALIZED VIEW IF EXISTS trending_books;
+DROP FUNCTION IF EXISTS trending_decay;
+ALTER TABLE users_books DROP COLUMN IF EXISTS "created_at";
+ALTER TABLE post_reactions DROP COLUMN IF EXISTS "created_at";
+ALTER TABLE comments DROP COLUMN IF EXISTS "created_at";
+ALTER TABLE posts DROP COLUMN IF EXISTS "created_at";
@@ -0,0 +1,167 @@
+-- Add down migration script here
+DROP FUNCTION IF EXISTS fetch_posts;
+
+CREATE OR REPLACE FUNCTION fetch_posts(request_uid BIGINT)
+RETURNS TABLE (
+  id BIGINT,
+  title TEXT,
+  content TEXT,
+  content_html TEXT,
+  content_spans JSONB,
+  spoiler BOOLEAN,
+  reveal_spoilers BOOLEAN,
+  book_id BIGINT,
+  author_name TEXT,
+  author_picture JSONB,
+  book_reaction BREACT,
+  reactions JSONB,
+  user_reaction PREACT
+) AS $$
+  SELECT
+    rv.id,
+    rv.title,
+    rv.content,
+    rv.content_html,
+    parse_spoilers(rv.content) AS content_spans,
+    rv.spoiler,
+    reveals_spoilers(request_uid, rv.book_id) AS reveal_spoilers,
+    rv.book_id,
+    rvu.name AS author_name,
+    rvu.picture AS author_picture,
+    rv.reaction AS book_reaction,
+    construct_reaction_object(prt) AS reactions,
+    upr.type AS user_reaction
+  FROM posts rv
+  JOIN users_view rvu
+  ON rv.author_id = rvu.id
+  LEFT JOIN post_reactions_tally prt
+  ON prt.post_id = rv.id
+  LEFT JOIN post_reactions upr
+  ON upr.post_id = rv.id AND upr.user_id = request_uid
+  WHERE NOT rvu.is_banned AND NOT is_restricted_by(request_uid, rvu.id);
+$$
+LANGUAGE sql;
+
+DROP FUNCTION IF EXISTS filter_books;
+
+-- The books matching `query`, along with whether they pass each of the other
+-- filters. Filters that are NULL let every book through. Keeping the filters
+-- apart lets facets be counted over the books passing all the others.
+CREATE OR REPLACE FUNCTION filter_books(
+  query TEXT,
+  query_lang TEXT,
+  languages TEXT[],
+  min_pages INT,
+  max_pages INT,
+  min_rating FLOAT8,
+  has_reviews BOOLEAN,
+  categories BIGINT[],
+  all_categories BOOLEAN,
+  authors BIGINT[],
+  all_authors BOOLEAN
+)
+RETURNS TABLE (
+  id BIGINT,
+  language TEXT,
+  pages INT,
+  rating FLOAT8,
+  reviewed BOOLEAN,
+  rank DECIMAL,
+  language_ok BOOLEAN,
+  pages_ok BOOLEAN,
+  rating_ok BOOLEAN,
+  reviews_ok BOOLEAN,
+  categories_ok BOOLEAN,
+  authors_ok BOOLEAN
+) AS $$
+  SELECT
+    b.id,
+    b.language,
+    b.pages,
+    r.rating,
+    r.reviewed,
+    CASE
+      WHEN q.query IS NULL THEN 0
+      ELSE ts_rank(b.text_search, q.query)
+    END + coalesce(books_boost_rating(brt), 0) AS rank,
+    languages IS NULL OR b.language = ANY(languages),
+    (min_pages IS NULL OR b.pages >= min_pages) AND (max_pages IS NULL OR b.pages <= max_pages),
+    min_rating IS NULL OR r.rating >= min_rating,
+    has_reviews IS NULL OR r.reviewed = has_reviews,
+    categories IS NULL OR CASE
+      WHEN all_categories THEN bc.ids @> categories
+      ELSE bc.ids && categories
+    END,
+    authors IS NULL OR CASE
+      WHEN all_authors THEN ba.ids @> authors
+      ELSE ba.ids && authors
+    END
+  FROM books b
+  LEFT JOIN book_reactions_tally brt ON brt.book_id = b.id
+  JOIN LATERAL (
+    SELECT
+      CASE WHEN brt.total > 0 THEN brt.like::FLOAT8 / brt.total ELSE NULL END AS rating,
+      coalesce(brt.total, 0) > 0 AS reviewed
+  ) r ON TRUE
+  JOIN LATERAL (
+    SELECT CASE
+      WHEN query IS NOT NULL THEN websearch_to_tsquery(bl.ts_config, query)
+      ELSE NULL
+    END AS query
+    FROM book_languages bl
+    WHERE bl.code = coalesce(query_lang, b.language)
+  ) q ON TRUE
+  JOIN LATERAL (
+    SELECT coalesce(array_agg(btc.category_id), '{}') AS ids
+    FROM book_to_category btc WHERE btc.book_id = b.id
+  ) bc ON TRUE
+  JOIN LATERAL (
+    SELECT coalesce(array_agg(bta.author_id), '{}') AS ids
+    FROM book_to_author bta WHERE bta.book_id = b.id
+  ) ba ON TRUE
+  WHERE q.query IS NULL OR b.text_search @@ q.query;
+$$
+LANGUAGE sql STABLE;
+
+DROP FUNCTION IF EXISTS books_bayesian_rating, books_mean_rating;
+
+CREATE OR REPLACE FUNCTION construct_book_reaction_object(rt ANYELEMENT)
+RETURNS JSONB LANGUAGE sql IMMUTABLE AS $$
+  SELECT CASE
+    WHEN rt IS NOT NULL THEN jsonb_build_object('total', rt.total, 'like', rt.like, 'dislike', rt.dislike)
+    ELSE NULL
+  END;
+$$;
+
+CREATE OR REPLACE FUNCTION recalculate_books_reactions() RETURNS trigger SECURITY DEFINER AS
+$trigger$
+BEGIN
+    -- An INSERT operation, insert new tally row if it doesn't already exist.
+    IF OLD IS NULL THEN
+        PERFORM FROM book_reactions_tally WHERE "book_id" = NEW."book_id";
+        IF NOT FOUND THEN
+            INSERT INTO book_reactions_tally ("book_id") VALUES (NEW."book_id");
+        END IF;
+    END IF;
+    UPDATE book_reactions_tally SET
+    -- Increase total if OLD is NULL (INSERT) and decrease if NEW is null (DELETE)
+    "total" = "total" - CASE WHEN OLD IS NULL THEN -1 WHEN NEW IS NULL THEN 1 ELSE 0 END,
+    "like" = "like" + calculate_book_reaction_delta(OLD, NEW, 'like'),
+    "dislike" = "dislike" + calculate_book_reaction_delta(OLD, NEW, 'dislike')
+    WHERE "book_id" = coalesce(NEW."book_id", OLD."book_id");
+    RETURN coalesce(NEW, OLD);
+END;
+$trigger$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER recalculate_books_reactions
+AFTER INSERT OR UPDATE OF "reaction" OR DELETE
+ON posts FOR EACH ROW 
+EXECUTE FUNCTION recalculate_books_reactions();
+
+ALTER TABLE book_reactions_tally
+  DROP COLUMN IF EXISTS "histogram",
+  DROP COLUMN IF EXISTS "rating_sum",
+  DROP COLUMN IF EXISTS "rated";
+
+ALTER TABLE posts DROP COLUMN IF EXISTS "rating";
@@ -0,0 +1,12 @@
+-- Add down migration script here
+ALTER TABLE users_books DROP COLUMN IF EXISTS "edition_id";
+
+ALTER TABLE books
+  DROP COLUMN IF EXISTS "series_position",
+  DROP COLUMN IF EXISTS "series_id";
+
+DROP TABLE IF EXISTS book_series;
+
+DROP TABLE IF EXISTS book_editions;
+
+DROP TYPE IF EXISTS BFORMAT;
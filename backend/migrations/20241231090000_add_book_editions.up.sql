@@ -0,0 +1,33 @@
+-- Add up migration script here
+CREATE TYPE BFORMAT AS ENUM ('hardcover', 'paperback', 'ebook', 'audiobook');
+
+-- A published edition of a book. ISBN-10s are stored as their ISBN-13.
+CREATE TABLE IF NOT EXISTS "book_editions" (
+  "id" BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  "book_id" BIGINT NOT NULL REFERENCES books(id) ON DELETE CASCADE,
+  "isbn" TEXT NOT NULL UNIQUE CHECK ("isbn" ~ '^[0-9]{13}$'),
+  "publisher" TEXT,
+  "published_on" DATE,
+  "format" BFORMAT NOT NULL,
+  "pages" INT CHECK ("pages" >= 0),
+  UNIQUE ("id", "book_id")
+);
+
+CREATE INDEX IF NOT EXISTS book_editions_book_id_idx ON book_editions(book_id);
+
+CREATE TABLE IF NOT EXISTS "book_series" (
+  "id" BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  "name" TEXT NOT NULL UNIQUE
+);
+
+ALTER TABLE books
+  ADD COLUMN IF NOT EXISTS "series_id" BIGINT REFERENCES book_series(id),
+  ADD COLUMN IF NOT EXISTS "series_position" INT,
+  ADD CONSTRAINT books_series_position_key UNIQUE ("series_id", "series_position"),
+  ADD CONSTRAINT books_series_position_check CHECK (("series_id" IS NULL) = ("series_position" IS NULL));
+
+-- Which edition was read. It must be an edition of the tracked book.
+ALTER TABLE users_books
+  ADD COLUMN IF NOT EXISTS "edition_id" BIGINT,
+  ADD CONSTRAINT users_books_edition_fkey FOREIGN KEY ("edition_id", "book_id")
+    REFERENCES book_editions("id", "book_id") ON DELETE SET NULL ("edition_id");
@@ -0,0 +1,88 @@
+-- Add down migration script here
+-- Restores the function ranking every pair of books at once.
+CREATE OR REPLACE FUNCTION refresh_recommendations(max_recommendations INT)
+RETURNS VOID AS $$
+  DELETE FROM book_recommendations;
+  DELETE FROM user_recommendations;
+
+  WITH readers AS (
+    SELECT DISTINCT ub.user_id, ub.book_id
+    FROM users_books ub
+    JOIN users u ON u.id = ub.user_id
+    WHERE NOT u.is_banned
+  ), likers AS (
+    SELECT DISTINCT p.author_id AS user_id, p.book_id
+    FROM posts p
+    JOIN users u ON u.id = p.author_id
+    WHERE p.reaction = 'like' AND NOT u.is_banned
+  ), pairs AS (
+    SELECT a.book_id, b.book_id AS recommended_id, 3.0 * count(*) AS score
+    FROM readers a
+    JOIN readers b ON b.user_id = a.user_id AND b.book_id <> a.book_id
+    GROUP BY a.book_id, b.book_id
+    UNION ALL
+    SELECT a.book_id, b.book_id, 2.0 * count(*)
+    FROM likers a
+    JOIN likers b ON b.user_id = a.user_id AND b.book_id <> a.book_id
+    GROUP BY a.book_id, b.book_id
+    UNION ALL
+    SELECT
+      a.id,
+      b.id,
+      cardinality(ARRAY(SELECT unnest(a.categories_raw) INTERSECT SELECT unnest(b.categories_raw)))
+        + 1.5 * cardinality(ARRAY(SELECT unnest(a.authors_raw) INTERSECT SELECT unnest(b.authors_raw)))
+    FROM books_view a
+    JOIN books_view b
+    ON b.id <> a.id AND (a.categories_raw && b.categories_raw OR a.authors_raw && b.authors_raw)
+  ), ranked AS (
+    SELECT
+      book_id,
+      recommended_id,
+      sum(score) AS score,
+      row_number() OVER (PARTITION BY book_id ORDER BY sum(score) DESC, recommended_id DESC) AS position
+    FROM pairs
+    GROUP BY book_id, recommended_id
+  )
+  INSERT INTO book_recommendations (book_id, recommended_id, score)
+  SELECT book_id, recommended_id, score
+  FROM ranked
+  WHERE position <= max_recommendations;
+
+  WITH seeds AS (
+    SELECT ub.user_id, ub.book_id
+    FROM users_books ub
+    UNION
+    SELECT p.author_id, p.book_id
+    FROM posts p
+    WHERE p.reaction = 'like'
+  ), seen AS (
+    SELECT ub.user_id, ub.book_id
+    FROM users_books ub
+    UNION
+    SELECT p.author_id, p.book_id
+    FROM posts p
+  ), ranked AS (
+    SELECT
+      s.user_id,
+      br.recommended_id AS book_id,
+      sum(br.score) AS score,
+      row_number() OVER (PARTITION BY s.user_id ORDER BY sum(br.score) DESC, br.recommended_id DESC) AS position
+    FROM seeds s
+    JOIN users u ON u.id = s.user_id
+    JOIN book_recommendations br ON br.book_id = s.book_id
+    WHERE NOT u.is_banned AND NOT EXISTS (
+      SELECT 1 FROM seen
+      WHERE seen.user_id = s.user_id AND seen.book_id = br.recommended_id
+    )
+    GROUP BY s.user_id, br.recommended_id
+  )
+  INSERT INTO user_recommendations (user_id, book_id, score)
+  SELECT user_id, book_id, score
+  FROM ranked
+  WHERE position <= max_recommendations;
+$$ LANGUAGE sql VOLATILE;
+
+DROP INDEX IF EXISTS book_to_author_author_idx;
+DROP INDEX IF EXISTS book_to_category_category_idx;
+DROP INDEX IF EXISTS posts_book_likes_idx;
+DROP INDEX IF EXISTS users_books_book_idx;
@@ -0,0 +1,86 @@
+-- Add up migration script here
+CREATE INDEX IF NOT EXISTS users_books_book_idx ON users_books (book_id);
+CREATE INDEX IF NOT EXISTS posts_book_likes_idx ON posts (book_id, author_id) WHERE reaction = 'like';
+CREATE INDEX IF NOT EXISTS book_to_category_category_idx ON book_to_category (category_id);
+CREATE INDEX IF NOT EXISTS book_to_author_author_idx ON book_to_author (author_id);
+
+-- Same as before, except that the related books are ranked one book at a
+-- time, starting from its own readers, likers, categories and authors, so
+-- that only its best `max_recommendations` are ever kept instead of every
+-- pair of books.
+CREATE OR REPLACE FUNCTION refresh_recommendations(max_recommendations INT)
+RETURNS VOID AS $$
+  DELETE FROM book_recommendations;
+  DELETE FROM user_recommendations;
+
+  INSERT INTO book_recommendations (book_id, recommended_id, score)
+  SELECT b.id, top.recommended_id, top.score
+  FROM books b
+  CROSS JOIN LATERAL (
+    SELECT recommended_id, sum(score) AS score
+    FROM (
+      SELECT other.book_id AS recommended_id, 3.0 * count(DISTINCT other.user_id) AS score
+      FROM users_books mine
+      JOIN users u ON u.id = mine.user_id
+      JOIN users_books other ON other.user_id = mine.user_id AND other.book_id <> mine.book_id
+      WHERE mine.book_id = b.id AND NOT u.is_banned
+      GROUP BY other.book_id
+      UNION ALL
+      SELECT other.book_id, 2.0 * count(DISTINCT other.author_id)
+      FROM posts mine
+      JOIN users u ON u.id = mine.author_id
+      JOIN posts other
+      ON other.author_id = mine.author_id AND other.book_id <> mine.book_id AND other.reaction = 'like'
+      WHERE mine.book_id = b.id AND mine.reaction = 'like' AND NOT u.is_banned
+      GROUP BY other.book_id
+      UNION ALL
+      SELECT other.book_id, count(*)
+      FROM book_to_category mine
+      JOIN book_to_category other ON other.category_id = mine.category_id AND other.book_id <> mine.book_id
+      WHERE mine.book_id = b.id
+      GROUP BY other.book_id
+      UNION ALL
+      SELECT other.book_id, 1.5 * count(*)
+      FROM book_to_author mine
+      JOIN book_to_author other ON other.author_id = mine.author_id AND other.book_id <> mine.book_id
+      WHERE mine.book_id = b.id
+      GROUP BY other.book_id
+    ) candidates
+    GROUP BY recommended_id
+    ORDER BY score DESC, recommended_id DESC
+    LIMIT max_recommendations
+  ) top;
+
+  WITH seeds AS (
+    SELECT ub.user_id, ub.book_id
+    FROM users_books ub
+    UNION
+    SELECT p.author_id, p.book_id
+    FROM posts p
+    WHERE p.reaction = 'like'
+  ), seen AS (
+    SELECT ub.user_id, ub.book_id
+    FROM users_books ub
+    UNION
+    SELECT p.author_id, p.book_id
+    FROM posts p
+  ), ranked AS (
+    SELECT
+      s.user_id,
+      br.recommended_id AS book_id,
+      sum(br.score) AS score,
+      row_number() OVER (PARTITION BY s.user_id ORDER BY sum(br.score) DESC, br.recommended_id DESC) AS position
+    FROM seeds s
+    JOIN users u ON u.id = s.user_id
+    JOIN book_recommendations br ON br.book_id = s.book_id
+    WHERE NOT u.is_banned AND NOT EXISTS (
+      SELECT 1 FROM seen
+      WHERE seen.user_id = s.user_id AND seen.book_id = br.recommended_id
+    )
+    GROUP BY s.user_id, br.recommended_id
+  )
+  INSERT INTO user_recommendations (user_id, book_id, score)
+  SELECT user_id, book_id, score
+  FROM ranked
+  WHERE position <= max_recommendations;
+$$ LANGUAGE sql VOLATILE;
@@ -0,0 +1,54 @@
+-- Add down migration script here
+DROP FUNCTION IF EXISTS refresh_trending;
+DROP FUNCTION IF EXISTS trending_decay(TIMESTAMPTZ, BIGINT, BIGINT);
+DROP TABLE IF EXISTS trending_posts;
+DROP TABLE IF EXISTS trending_books;
+
+-- How much activity that happened `at` counts towards trending. It halves
+-- every day, and activity older than a week is not counted at all.
+CREATE OR REPLACE FUNCTION trending_decay(at TIMESTAMPTZ)
+RETURNS FLOAT8 AS $$
+  SELECT CASE
+    WHEN at IS NULL OR at < NOW() - INTERVAL '7 days' THEN 0
+    ELSE power(0.5, extract(EPOCH FROM NOW() - at) / 86400)
+  END;
+$$ LANGUAGE sql STABLE;
+
+-- Books by their recent reviews, started trackers, and reactions to their
+-- reviews. Activity of banned users is left out.
+CREATE MATERIALIZED VIEW IF NOT EXISTS trending_books AS
+  SELECT a.book_id, sum(a.weight * trending_decay(a.at)) AS score
+  FROM (
+    SELECT p.book_id, p.created_at AS at, 3.0 AS weight, p.author_id AS user_id
+    FROM posts p
+    UNION ALL
+    SELECT ub.book_id, ub.created_at, 2.0, ub.user_id
+    FROM users_books ub
+    UNION ALL
+    SELECT p.book_id, pr.created_at, 1.0, pr.user_id
+    FROM post_reactions pr
+    JOIN posts p ON p.id = pr.post_id
+  ) a
+  JOIN users u ON u.id = a.user_id
+  WHERE a.at >= NOW() - INTERVAL '7 days' AND NOT u.is_banned
+  GROUP BY a.book_id;
+
+CREATE UNIQUE INDEX IF NOT EXISTS trending_books_book_id_idx ON trending_books(book_id);
+CREATE INDEX IF NOT EXISTS trending_books_score_idx ON trending_books(score DESC);
+
+-- Reviews by how fast they are getting reactions and comments.
+CREATE MATERIALIZED VIEW IF NOT EXISTS trending_posts AS
+  SELECT a.post_id, sum(a.weight * trending_decay(a.at)) AS score
+  FROM (
+    SELECT pr.post_id, pr.created_at AS at, 1.0 AS weight, pr.user_id
+    FROM post_reactions pr
+    UNION ALL
+    SELECT c.post_id, c.created_at, 2.0, c.author_id
+    FROM comments c
+  ) a
+  JOIN users u ON u.id = a.user_id
+  WHERE a.at >= NOW() - INTERVAL '7 days' AND NOT u.is_banned
+  GROUP BY a.post_id;
+
+CREATE UNIQUE INDEX IF NOT EXISTS trending_posts_post_id_idx ON trending_posts(post_id);
+CREATE INDEX IF NOT EXISTS trending_posts_score_idx ON trending_posts(score DESC);
@@ -0,0 +1,71 @@
+-- Add up migration script here
+-- Materialized views cannot take parameters, so trending is kept in tables
+-- refreshed by `refresh_trending` with the window and half-life it is given.
+DROP MATERIALIZED VIEW IF EXISTS trending_posts;
+DROP MATERIALIZED VIEW IF EXISTS trending_books;
+DROP FUNCTION IF EXISTS trending_decay(TIMESTAMPTZ);
+
+CREATE TABLE IF NOT EXISTS "trending_books" (
+  "book_id" BIGINT PRIMARY KEY REFERENCES books(id) ON DELETE CASCADE,
+  "score" FLOAT8 NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS trending_books_score_idx ON trending_books(score DESC);
+
+CREATE TABLE IF NOT EXISTS "trending_posts" (
+  "post_id" BIGINT PRIMARY KEY REFERENCES posts(id) ON DELETE CASCADE,
+  "score" FLOAT8 NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS trending_posts_score_idx ON trending_posts(score DESC);
+
+-- How much activity that happened `at` counts towards trending. It halves
+-- every `half_life` seconds, and activity older than `window` seconds is not
+-- counted at all.
+CREATE OR REPLACE FUNCTION trending_decay(at TIMESTAMPTZ, "window" BIGINT, half_life BIGINT)
+RETURNS FLOAT8 AS $$
+  SELECT CASE
+    WHEN at IS NULL OR at < NOW() - make_interval(secs => "window") THEN 0
+    ELSE power(0.5, extract(EPOCH FROM NOW() - at) / half_life)
+  END;
+$$ LANGUAGE sql STABLE;
+
+-- Recomputes the scores of trending books, by their recent reviews, started
+-- trackers, and reactions to their reviews, and of trending reviews, by how
+-- fast they are getting reactions and comments. Activity of banned users is
+-- left out.
+CREATE OR REPLACE FUNCTION refresh_trending("window" BIGINT, half_life BIGINT)
+RETURNS VOID AS $$
+  DELETE FROM trending_books;
+  DELETE FROM trending_posts;
+
+  INSERT INTO trending_books (book_id, score)
+  SELECT a.book_id, sum(a.weight * trending_decay(a.at, "window", half_life))
+  FROM (
+    SELECT p.book_id, p.created_at AS at, 3.0 AS weight, p.author_id AS user_id
+    FROM posts p
+    UNION ALL
+    SELECT ub.book_id, ub.created_at, 2.0, ub.user_id
+    FROM users_books ub
+    UNION ALL
+    SELECT p.book_id, pr.created_at, 1.0, pr.user_id
+    FROM post_reactions pr
+    JOIN posts p ON p.id = pr.post_id
+  ) a
+  JOIN users u ON u.id = a.user_id
+  WHERE a.at >= NOW() - make_interval(secs => "window") AND NOT u.is_banned
+  GROUP BY a.book_id;
+
+  INSERT INTO trending_posts (post_id, score)
+  SELECT a.post_id, sum(a.weight * trending_decay(a.at, "window", half_life))
+  FROM (
+    SELECT pr.post_id, pr.created_at AS at, 1.0 AS weight, pr.user_id
+    FROM post_reactions pr
+    UNION ALL
+    SELECT c.post_id, c.created_at, 2.0, c.author_id
+    FROM comments c
+  ) a
+  JOIN users u ON u.id = a.user_id
+  WHERE a.at >= NOW() - make_interval(secs => "window") AND NOT u.is_banned
+  GROUP BY a.post_id;
+$$ LANGUAGE sql VOLATILE;
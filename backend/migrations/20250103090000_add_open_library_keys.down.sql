@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS open_library_works;
+DROP TABLE IF EXISTS open_library_authors;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+-- The Open Library authors and works imported so far, by key, which works
+-- and editions refer to. Keeping them lets those be imported in batches,
+-- or by another import.
+CREATE TABLE IF NOT EXISTS "open_library_authors" (
+  "key" TEXT PRIMARY KEY,
+  "name" TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS "open_library_works" (
+  "key" TEXT PRIMARY KEY,
+  "book_id" BIGINT NOT NULL REFERENCES books(id) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS import_jobs;
+DROP TYPE IF EXISTS ISTATUS;
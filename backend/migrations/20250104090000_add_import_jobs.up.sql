@@ -0,0 +1,14 @@
+-- Add up migration script here
+CREATE TYPE ISTATUS AS ENUM ('running', 'done', 'failed');
+
+-- Imports requested over HTTP, which run in the background. The report is
+-- only set once the import is done, and the error once it failed.
+CREATE TABLE IF NOT EXISTS "import_jobs" (
+  "id" BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  "user_id" BIGINT REFERENCES users(id) ON DELETE SET NULL,
+  "status" ISTATUS NOT NULL DEFAULT 'running',
+  "report" JSONB,
+  "error" TEXT,
+  "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  "finished_at" TIMESTAMPTZ
+);
@@ -1,9 +1,13 @@
-use crate::{routes, settings::SETTINGS};
-use aws_sdk_s3 as s3;
+use crate::{
+    jobs, routes,
+    scanner::{self, Scanner},
+    settings::SETTINGS,
+    storage::{self, StorageBackend},
+};
 use axum::{
     extract::DefaultBodyLimit,
     http::{header, HeaderName, HeaderValue, Method},
-    routing::{get, post},
+    routing::{get, post, put},
     serve::Serve,
     Router,
 };
@@ -11,14 +15,15 @@ use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
     Pool, Postgres,
 };
-use std::{str::FromStr, time::Duration};
+use std::{str::FromStr, sync::Arc, time::Duration};
 use tower::ServiceBuilder;
 use tower_http::{cors::CorsLayer, set_header::SetResponseHeaderLayer, timeout::TimeoutLayer};
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: Pool<Postgres>,
-    pub s3: s3::Client,
+    pub storage: Arc<dyn StorageBackend>,
+    pub scanner: Arc<dyn Scanner>,
     pub redis_client: redis::Client,
 }
 
@@ -29,9 +34,6 @@ pub struct Application {
 
 impl Application {
     pub async fn build() -> Result<Self, std::io::Error> {
-        let s3_config = aws_config::load_from_env().await;
-        let client = s3::Client::new(&s3_config);
-
         let address = format!("{}:{}", SETTINGS.app.host, SETTINGS.app.port);
 
         let db_uri = std::env::var("DATABASE_URL").expect("Failed to read database URI");
@@ -46,16 +48,19 @@ impl Application {
         let redis_client = redis::Client::open(SETTINGS.redis.uri.as_str())
             .expect("Failed to create a Redis client");
 
-        // let hdfs = Arc::new(
-        //     AppHdfs::new("hdfs://localhost:9000", "ducanh").expect("Failed to initiate HDFS"),
-        // );
+        let storage = storage::from_settings(&SETTINGS.storage).await;
+
+        let scanner = scanner::from_settings(&SETTINGS.scanner);
 
         let app_state = AppState {
             pool,
-            s3: client,
+            storage,
+            scanner,
             redis_client,
         };
 
+        jobs::spawn(&app_state).await;
+
         let listener = tokio::net::TcpListener::bind(&address).await?;
         let port = listener.local_addr().unwrap().port();
         let cors = CorsLayer::new()
@@ -86,7 +91,18 @@ impl Application {
                 post(routes::books::create).get(routes::books::read),
             )
             .route("/books/:slug", get(routes::books::read_slug))
+            .route("/books/:slug/editions", post(routes::books::create_edition))
+            .route("/books/:slug/series", put(routes::books::update_series))
+            .route("/books/series", post(routes::books::create_series))
+            .route("/books/import", post(routes::books::import))
+            .route("/books/import/:id", get(routes::books::read_import))
+            .route(
+                "/books/:slug/recommendations",
+                get(routes::recommendations::read_book),
+            )
             .route("/books/categories", get(routes::books::read_categories))
+            .route("/books/suggest", get(routes::books::suggest))
+            .route("/books/trending", get(routes::books::trending))
             .route("/books/metadata", get(routes::books::read_metadata))
             .route("/books/read", post(routes::books::create_tracker))
             .route("/books/tracker/:book", get(routes::books::fetch_tracker))
@@ -97,6 +113,7 @@ impl Application {
                     .patch(routes::posts::update)
                     .delete(routes::posts::delete),
             )
+            .route("/posts/trending", get(routes::posts::trending))
             .route("/posts/:id", get(routes::posts::read_slug))
             .route(
                 "/comments",
@@ -110,12 +127,45 @@ impl Application {
                 "/reactions",
                 post(routes::reactions::create).delete(routes::reactions::delete),
             )
+            .route("/tags/:tag", get(routes::tags::read))
+            .route("/search", get(routes::search::search))
             .route("/auth/authenticate", post(routes::auth::authenticate))
             .route("/auth/confirm", post(routes::auth::confirm))
             .route("/auth/register", post(routes::auth::register))
             .route("/auth/login", post(routes::auth::login))
             .route("/users/:name/metadata", get(routes::users::read_metadata))
+            .route(
+                "/users/:name/ban",
+                post(routes::users::ban).delete(routes::users::unban),
+            )
+            .route(
+                "/users/:name/block",
+                post(routes::users::block).delete(routes::users::unblock),
+            )
+            .route(
+                "/users/:name/mute",
+                post(routes::users::mute).delete(routes::users::unmute),
+            )
+            .route("/users/restrictions", get(routes::users::read_restrictions))
+            .route(
+                "/users/recommendations",
+                get(routes::recommendations::read_user),
+            )
+            .route(
+                "/users/preferences",
+                get(routes::users::read_preferences).patch(routes::users::update_preferences),
+            )
+            .route("/assets", get(routes::files::list))
+            .route("/assets/usage", get(routes::files::read_usage))
+            .route(
+                "/assets/files/:id",
+                get(routes::files::read).delete(routes::files::delete),
+            )
+            .route("/assets/files/:id/sign", post(routes::files::sign))
             .route("/assets/upload", post(routes::files::upload))
+            .route("/assets/presign", post(routes::files::presign))
+            .route("/assets/complete", post(routes::files::complete))
+            .route("/assets/gc", post(routes::files::collect))
             .route("/assets/*path", get(routes::files::load))
             .with_state(app_state)
             .layer(ServiceBuilder::new().layer(cors).layer(robots))
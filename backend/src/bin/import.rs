@@ -0,0 +1,88 @@
+//! Imports books from files on disk, see `jobs::import`, then prints what
+//! was imported as JSON.
+use blisk_backend::{
+    jobs::import::{self, Format},
+    settings::SETTINGS,
+};
+use sqlx::postgres::PgPoolOptions;
+use std::{path::PathBuf, process::ExitCode};
+
+const USAGE: &str =
+    "Usage: import --format <csv|jsonl|open_library> [--language <code>] [--batch <rows>] <file>...";
+
+struct Args {
+    format: Format,
+    language: String,
+    batch: usize,
+    paths: Vec<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut format = None;
+    let mut language = import::DEFAULT_LANGUAGE.to_owned();
+    let mut batch = SETTINGS.import.batch;
+    let mut paths = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = args.next().ok_or("--format needs a value")?;
+                format = Some(value.parse::<Format>().map_err(|err| err.to_string())?);
+            }
+            "--language" => language = args.next().ok_or("--language needs a value")?,
+            "--batch" => {
+                batch = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|batch| *batch > 0)
+                    .ok_or("--batch needs a positive number")?;
+            }
+            "-h" | "--help" => return Err(USAGE.to_owned()),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let format = format.ok_or("--format is required")?;
+    if paths.is_empty() {
+        return Err("at least one file is required".to_owned());
+    }
+    Ok(Args {
+        format,
+        language,
+        batch,
+        paths,
+    })
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let db_uri = std::env::var("DATABASE_URL").expect("Failed to read database URI");
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&db_uri)
+        .await
+        .expect("Failed to connect to the database");
+
+    match import::run(&pool, &args.paths, args.format, &args.language, args.batch).await {
+        Ok(report) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).expect("Failed to serialize the report")
+            );
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Failed to import books: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
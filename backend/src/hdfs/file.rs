@@ -1,169 +1,361 @@
-// use super::{bindgen, AppHdfs};
-// use std::{
-//     ffi::{c_void, CStr},
-//     io::{Error as IoError, Read, Result as IoResult, Seek, SeekFrom, Write},
-// };
-
-// pub struct FileInfo {
-//     pub kind: u32,
-//     pub name: String,
-//     pub last_mod: i64,
-//     pub size: i64,
-//     pub replication: i16,
-//     pub block_size: i64,
-//     pub owner: String,
-//     pub group: String,
-//     pub permissions: i16,
-//     pub last_access: i64,
-// }
-
-// impl From<bindgen::hdfsFileInfo> for FileInfo {
-//     fn from(value: bindgen::hdfsFileInfo) -> Self {
-//         let c_name = unsafe { CStr::from_ptr(value.mName) };
-//         let c_owner = unsafe { CStr::from_ptr(value.mOwner) };
-//         let c_group = unsafe { CStr::from_ptr(value.mGroup) };
-//         FileInfo {
-//             kind: value.mKind,
-//             name: c_name
-//                 .to_str()
-//                 .expect("Filename is not valid UTF-8!")
-//                 .to_owned(),
-//             last_mod: value.mLastMod,
-//             size: value.mSize,
-//             replication: value.mReplication,
-//             block_size: value.mBlockSize,
-//             owner: c_owner
-//                 .to_str()
-//                 .expect("Owner is not valid UTF-8!")
-//                 .to_owned(),
-//             group: c_group
-//                 .to_str()
-//                 .expect("Group is not valid UTF-8!")
-//                 .to_owned(),
-//             permissions: value.mPermissions,
-//             last_access: value.mLastAccess,
-//         }
-//     }
-// }
-
-// #[derive(Clone)]
-// pub struct File<'a> {
-//     pub(super) hdfs: &'a AppHdfs,
-//     pub(super) file: bindgen::hdfsFile,
-//     pub(super) path: String,
-// }
-
-// unsafe impl Send for File<'_> {}
-
-// unsafe impl Sync for File<'_> {}
-
-// impl File<'_> {
-//     pub fn at(&self, buf: &mut [u8], offset: u64) -> IoResult<usize> {
-//         let n = unsafe {
-//             bindgen::hdfsPread(
-//                 self.hdfs.fs,
-//                 self.file,
-//                 offset as i64,
-//                 buf.as_ptr() as *mut c_void,
-//                 buf.len() as i32,
-//             )
-//         };
-
-//         if n == -1 {
-//             return Err(IoError::last_os_error());
-//         }
-
-//         Ok(n as usize)
-//     }
-
-//     fn seek_inner(&self, offset: i64) -> IoResult<()> {
-//         let n = unsafe { bindgen::hdfsSeek(self.hdfs.fs, self.file, offset) };
-
-//         if n == -1 {
-//             return Err(IoError::last_os_error());
-//         }
-
-//         Ok(())
-//     }
-
-//     fn tell_inner(&self) -> IoResult<i64> {
-//         let n = unsafe { bindgen::hdfsTell(self.hdfs.fs, self.file) };
-
-//         if n == -1 {
-//             return Err(IoError::last_os_error());
-//         }
-
-//         Ok(n)
-//     }
-// }
-
-// impl Read for File<'_> {
-//     fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
-//         let n = unsafe {
-//             bindgen::hdfsRead(
-//                 self.hdfs.fs,
-//                 self.file,
-//                 buf.as_ptr() as *mut c_void,
-//                 buf.len() as i32,
-//             )
-//         };
-
-//         if n == -1 {
-//             return Err(IoError::last_os_error());
-//         }
-
-//         Ok(n as usize)
-//     }
-// }
-
-// impl Seek for File<'_> {
-//     fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
-//         match pos {
-//             SeekFrom::Start(n) => {
-//                 self.seek_inner(n as i64)?;
-//                 Ok(n)
-//             }
-//             SeekFrom::Current(n) => {
-//                 let current = self.tell_inner()?;
-//                 let offset = (current + n) as u64;
-//                 self.seek_inner(offset as i64)?;
-//                 Ok(offset)
-//             }
-//             SeekFrom::End(n) => {
-//                 let meta = self.hdfs.read(&self.path)?;
-//                 let offset = meta.size as i64 + n;
-//                 self.seek_inner(offset)?;
-//                 Ok(offset as u64)
-//             }
-//         }
-//     }
-// }
-
-// impl Write for File<'_> {
-//     fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
-//         let n = unsafe {
-//             bindgen::hdfsWrite(
-//                 self.hdfs.fs,
-//                 self.file,
-//                 buf.as_ptr() as *const c_void,
-//                 buf.len() as i32,
-//             )
-//         };
-
-//         if n == -1 {
-//             return Err(IoError::last_os_error());
-//         }
-
-//         Ok(n as usize)
-//     }
-
-//     fn flush(&mut self) -> IoResult<()> {
-//         let n = unsafe { bindgen::hdfsFlush(self.hdfs.fs, self.file) };
-
-//         if n == -1 {
-//             return Err(IoError::last_os_error());
-//         }
-
-//         Ok(())
-//     }
-// }
+use super::{bindgen, AppHdfs};
+use crate::utils::os::clear_errno;
+use std::{
+    ffi::{c_void, CStr},
+    future::Future,
+    io::{Error as IoError, Read, Result as IoResult, Seek, SeekFrom, Write},
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf},
+    task::JoinHandle,
+};
+
+pub struct FileInfo {
+    pub kind: u32,
+    pub name: String,
+    pub last_mod: i64,
+    pub size: i64,
+    pub replication: i16,
+    pub block_size: i64,
+    pub owner: String,
+    pub group: String,
+    pub permissions: i16,
+    pub last_access: i64,
+}
+
+impl FileInfo {
+    pub fn is_dir(&self) -> bool {
+        self.kind == bindgen::tObjectKind_kObjectKindDirectory
+    }
+}
+
+/// Returns `value` as a `String`, or an error naming `what` when it is
+/// not valid UTF-8.
+fn to_string(value: &CStr, what: &str) -> IoResult<String> {
+    value.to_str().map(str::to_owned).map_err(|_| {
+        IoError::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{what} is not valid UTF-8!"),
+        )
+    })
+}
+
+impl TryFrom<bindgen::hdfsFileInfo> for FileInfo {
+    type Error = IoError;
+
+    fn try_from(value: bindgen::hdfsFileInfo) -> IoResult<Self> {
+        let c_name = unsafe { CStr::from_ptr(value.mName) };
+        let c_owner = unsafe { CStr::from_ptr(value.mOwner) };
+        let c_group = unsafe { CStr::from_ptr(value.mGroup) };
+        Ok(FileInfo {
+            kind: value.mKind,
+            name: to_string(c_name, "Filename")?,
+            last_mod: value.mLastMod,
+            size: value.mSize,
+            replication: value.mReplication,
+            block_size: value.mBlockSize,
+            owner: to_string(c_owner, "Owner")?,
+            group: to_string(c_group, "Group")?,
+            permissions: value.mPermissions,
+            last_access: value.mLastAccess,
+        })
+    }
+}
+
+/// A file opened with [`AppHdfs::open`]. It is closed when dropped, but
+/// only [`File::close`] tells whether closing it succeeded.
+pub struct File {
+    pub(super) hdfs: Arc<AppHdfs>,
+    pub(super) file: bindgen::hdfsFile,
+    pub(super) path: String,
+}
+
+unsafe impl Send for File {}
+
+unsafe impl Sync for File {}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        if !self.file.is_null() {
+            unsafe { bindgen::hdfsCloseFile(self.hdfs.fs, self.file) };
+        }
+    }
+}
+
+impl File {
+    /// Closes the file. HDFS only finalizes what was written to a file once
+    /// it is closed, so what was written is lost when this fails.
+    pub fn close(mut self) -> IoResult<()> {
+        clear_errno();
+
+        let n = unsafe { bindgen::hdfsCloseFile(self.hdfs.fs, self.file) };
+        // The handle is freed even when closing fails.
+        self.file = std::ptr::null_mut();
+
+        if n == -1 {
+            return Err(IoError::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    pub fn at(&self, buf: &mut [u8], offset: u64) -> IoResult<usize> {
+        let n = unsafe {
+            bindgen::hdfsPread(
+                self.hdfs.fs,
+                self.file,
+                offset as i64,
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len() as i32,
+            )
+        };
+
+        if n == -1 {
+            return Err(IoError::last_os_error());
+        }
+
+        Ok(n as usize)
+    }
+
+    fn seek_inner(&self, offset: i64) -> IoResult<()> {
+        let n = unsafe { bindgen::hdfsSeek(self.hdfs.fs, self.file, offset) };
+
+        if n == -1 {
+            return Err(IoError::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn tell_inner(&self) -> IoResult<i64> {
+        let n = unsafe { bindgen::hdfsTell(self.hdfs.fs, self.file) };
+
+        if n == -1 {
+            return Err(IoError::last_os_error());
+        }
+
+        Ok(n)
+    }
+
+    /// Turns this file into one that implements `AsyncRead` and `AsyncWrite`.
+    pub fn into_async(self) -> AsyncFile {
+        AsyncFile {
+            state: State::Idle(Some((self, Vec::new()))),
+        }
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let n = unsafe {
+            bindgen::hdfsRead(
+                self.hdfs.fs,
+                self.file,
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len() as i32,
+            )
+        };
+
+        if n == -1 {
+            return Err(IoError::last_os_error());
+        }
+
+        Ok(n as usize)
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
+        match pos {
+            SeekFrom::Start(n) => {
+                self.seek_inner(n as i64)?;
+                Ok(n)
+            }
+            SeekFrom::Current(n) => {
+                let current = self.tell_inner()?;
+                let offset = (current + n) as u64;
+                self.seek_inner(offset as i64)?;
+                Ok(offset)
+            }
+            SeekFrom::End(n) => {
+                let meta = self.hdfs.read(&self.path)?;
+                let offset = meta.size + n;
+                self.seek_inner(offset)?;
+                Ok(offset as u64)
+            }
+        }
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let n = unsafe {
+            bindgen::hdfsWrite(
+                self.hdfs.fs,
+                self.file,
+                buf.as_ptr() as *const c_void,
+                buf.len() as i32,
+            )
+        };
+
+        if n == -1 {
+            return Err(IoError::last_os_error());
+        }
+
+        Ok(n as usize)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        let n = unsafe { bindgen::hdfsFlush(self.hdfs.fs, self.file) };
+
+        if n == -1 {
+            return Err(IoError::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+/// The largest chunk handed to a single blocking read or write.
+const MAX_BUF: usize = 2 * 1024 * 1024;
+
+enum Operation {
+    Read(IoResult<usize>),
+    Write(IoResult<()>),
+    Flush(IoResult<()>),
+}
+
+enum State {
+    /// `None` if a blocking operation panicked, leaving the file unusable.
+    Idle(Option<(File, Vec<u8>)>),
+    Busy(JoinHandle<(File, Vec<u8>, Operation)>),
+}
+
+/// A [`File`] whose blocking calls are run on tokio's blocking thread pool,
+/// similar to how `tokio::fs::File` works. Writes are buffered, so call
+/// `flush` to find out whether they succeeded.
+pub struct AsyncFile {
+    state: State,
+}
+
+fn unusable() -> IoError {
+    IoError::other("the file is unusable because a previous operation panicked")
+}
+
+impl AsyncFile {
+    /// Waits for the pending blocking operation, if any, to finish.
+    fn poll_complete(&mut self, cx: &mut Context<'_>) -> Poll<IoResult<Option<Operation>>> {
+        let State::Busy(handle) = &mut self.state else {
+            return Poll::Ready(Ok(None));
+        };
+        match ready!(Pin::new(handle).poll(cx)) {
+            Ok((file, buf, operation)) => {
+                self.state = State::Idle(Some((file, buf)));
+                Poll::Ready(Ok(Some(operation)))
+            }
+            Err(err) => {
+                self.state = State::Idle(None);
+                Poll::Ready(Err(IoError::other(err)))
+            }
+        }
+    }
+
+    fn take(&mut self) -> IoResult<(File, Vec<u8>)> {
+        match &mut self.state {
+            State::Idle(inner) => inner.take().ok_or_else(unusable),
+            State::Busy(_) => unreachable!("the previous operation has not completed"),
+        }
+    }
+
+    /// Flushes the pending writes, then closes the file, see [`File::close`].
+    pub async fn close(mut self) -> IoResult<()> {
+        self.flush().await?;
+        let (file, _) = self.take()?;
+        tokio::task::spawn_blocking(move || file.close())
+            .await
+            .map_err(IoError::other)?
+    }
+}
+
+impl AsyncRead for AsyncFile {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        dst: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        loop {
+            match ready!(this.poll_complete(cx))? {
+                Some(Operation::Read(res)) => {
+                    let n = res?;
+                    if let State::Idle(Some((_, buf))) = &this.state {
+                        dst.put_slice(&buf[..n]);
+                    }
+                    return Poll::Ready(Ok(()));
+                }
+                Some(Operation::Write(Err(err)) | Operation::Flush(Err(err))) => {
+                    return Poll::Ready(Err(err));
+                }
+                Some(_) => {}
+                None => {
+                    let (mut file, mut buf) = this.take()?;
+                    buf.resize(dst.remaining().min(MAX_BUF), 0);
+                    this.state = State::Busy(tokio::task::spawn_blocking(move || {
+                        let res = file.read(&mut buf);
+                        (file, buf, Operation::Read(res))
+                    }));
+                }
+            }
+        }
+    }
+}
+
+impl AsyncWrite for AsyncFile {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        src: &[u8],
+    ) -> Poll<IoResult<usize>> {
+        let this = self.get_mut();
+        if let Some(Operation::Write(Err(err)) | Operation::Flush(Err(err))) =
+            ready!(this.poll_complete(cx))?
+        {
+            return Poll::Ready(Err(err));
+        }
+        let (mut file, mut buf) = this.take()?;
+        let n = src.len().min(MAX_BUF);
+        buf.clear();
+        buf.extend_from_slice(&src[..n]);
+        this.state = State::Busy(tokio::task::spawn_blocking(move || {
+            let res = file.write_all(&buf);
+            (file, buf, Operation::Write(res))
+        }));
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        loop {
+            match ready!(this.poll_complete(cx))? {
+                Some(Operation::Flush(res)) => return Poll::Ready(res),
+                Some(Operation::Write(Err(err))) => return Poll::Ready(Err(err)),
+                Some(_) => {}
+                None => {
+                    let (mut file, buf) = this.take()?;
+                    this.state = State::Busy(tokio::task::spawn_blocking(move || {
+                        let res = file.flush();
+                        (file, buf, Operation::Flush(res))
+                    }));
+                }
+            }
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        // The file itself is closed when it is dropped, or by `close`.
+        self.poll_flush(cx)
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{
+    storage::StorageBackend,
+    utils::{errors::AppError, uploads::UploadsError},
+};
+use sqlx::PgPool;
+use std::time::{Duration, SystemTime};
+use tracing::{event, instrument, Level};
+
+const BATCH_SIZE: usize = 1000;
+
+/// What a collection deleted, or would have deleted during a dry run.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct Report {
+    pub dry_run: bool,
+    /// Files whose upload was never completed, or which are no longer used.
+    pub files: Vec<i64>,
+    /// Stored objects that no file points to.
+    pub objects: Vec<String>,
+}
+
+/// Returns the key of the file `key` belongs to. Transformed copies of
+/// an object are stored under `cache/{key}/`, and quarantined files
+/// under `quarantine/{key}`.
+fn source_key(key: &str) -> Option<&str> {
+    if let Some(rest) = key.strip_prefix("cache/") {
+        return rest.split_once('/').map(|(source, _)| source);
+    }
+    Some(key.strip_prefix("quarantine/").unwrap_or(key))
+}
+
+/// Deletes the files that were orphaned for longer than `grace`, then the
+/// stored objects that no file points to. Objects written to storage by a
+/// transaction that was rolled back end up in the latter.
+#[instrument(name = "Collecting orphaned files", skip(pool, storage))]
+pub async fn collect(
+    pool: &PgPool,
+    storage: &dyn StorageBackend,
+    grace: Duration,
+    dry_run: bool,
+) -> Result<Report, AppError> {
+    let mut report = Report {
+        dry_run,
+        ..Default::default()
+    };
+
+    // Variants are removed along with their file by `ON DELETE CASCADE`.
+    report.files = sqlx::query_scalar!(
+        r#"WITH orphans AS (
+            SELECT f.id FROM files f
+            WHERE f.variant IS NULL AND (
+                (f.status = 'pending' AND f.created_at < NOW() - make_interval(secs => $1))
+                OR (
+                    f.detached_at < NOW() - make_interval(secs => $1)
+                    AND NOT EXISTS (SELECT 1 FROM books b WHERE b.cover_id = f.id OR b.spine_id = f.id)
+                    AND NOT EXISTS (SELECT 1 FROM users u WHERE u.picture_id = f.id)
+                )
+            )
+            FOR UPDATE SKIP LOCKED
+        ), deleted AS (
+            DELETE FROM files WHERE NOT $2 AND id IN (SELECT id FROM orphans)
+        )
+        SELECT id AS "id!" FROM orphans ORDER BY id"#,
+        grace.as_secs_f64(),
+        dry_run,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let threshold = SystemTime::now() - grace;
+    let keys = storage.list("").await.map_err(UploadsError::from)?;
+    let keys = keys
+        .iter()
+        .filter_map(|key| source_key(key).map(|source| (key.as_str(), source)))
+        .collect::<Vec<_>>();
+    for batch in keys.chunks(BATCH_SIZE) {
+        let (keys, sources): (Vec<_>, Vec<_>) = batch.iter().copied().unzip();
+        // During a dry run, the files found above still exist and have
+        // to be left out explicitly.
+        let orphans = sqlx::query_scalar!(
+            r#"SELECT o.key AS "key!" FROM UNNEST($1::TEXT[], $2::TEXT[]) AS o(key, source)
+            WHERE NOT EXISTS (
+                SELECT 1 FROM files f
+                WHERE f.key = o.source
+                AND f.id <> ALL($3) AND (f.parent_id IS NULL OR f.parent_id <> ALL($3))
+            )"#,
+            &keys as &[&str],
+            &sources as &[&str],
+            &report.files,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        for key in orphans {
+            // Objects may belong to an upload whose transaction has not been
+            // committed yet, so only old enough ones are collected.
+            let metadata = storage.head(&key).await.map_err(UploadsError::from)?;
+            if !metadata
+                .last_modified
+                .is_some_and(|last_modified| last_modified < threshold)
+            {
+                continue;
+            }
+            if !dry_run {
+                storage.delete(&key).await.map_err(UploadsError::from)?;
+            }
+            report.objects.push(key);
+        }
+    }
+
+    event!(
+        Level::INFO,
+        files = report.files.len(),
+        objects = report.objects.len(),
+        dry_run,
+        "collected orphaned files"
+    );
+    Ok(report)
+}
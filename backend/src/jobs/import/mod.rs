@@ -0,0 +1,803 @@
+use crate::{
+    routes::books::{BookFormat, BooksError},
+    utils::{constants::SLUG_REGEX, errors::AppError, isbn, validators::isbn_is_valid},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::MultiGzDecoder;
+use sqlx::{Acquire, PgConnection, PgPool};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, ErrorKind, Lines, Read},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tracing::{event, instrument, Level};
+use validator::Validate;
+
+pub mod openlibrary;
+
+/// The language of Open Library works when none is given, as they do not
+/// have one.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("file {0} cannot be found")]
+    FileNotFound(String),
+    #[error("format {0} is not supported")]
+    UnsupportedFormat(String),
+    #[error("file is malformed: {0}")]
+    Malformed(String),
+    #[error("work {0} must be imported before its editions")]
+    WorkNotFound(String),
+    #[error("import job {0} cannot be found")]
+    JobNotFound(i64),
+    #[error("import stopped unexpectedly")]
+    Panicked,
+    #[error("import was interrupted by a restart")]
+    Interrupted,
+    #[error("error while reading a file: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// What the files of an import contain. Files ending with `.gz` are
+/// decompressed while they are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// A header, then a book and at most one of its editions per row.
+    /// Authors and categories are separated by `;`.
+    Csv,
+    /// A book and its editions per line, as a JSON object.
+    Jsonl,
+    /// Open Library authors, works and editions dumps, see
+    /// <https://openlibrary.org/developers/dumps>.
+    OpenLibrary,
+}
+
+impl FromStr for Format {
+    type Err = ImportError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "csv" => Ok(Format::Csv),
+            "jsonl" => Ok(Format::Jsonl),
+            "open_library" => Ok(Format::OpenLibrary),
+            other => Err(ImportError::UnsupportedFormat(other.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize, Validate)]
+pub struct EditionRecord {
+    /// An ISBN-10 or an ISBN-13, with or without hyphens.
+    #[validate(custom(function = "isbn_is_valid"))]
+    pub isbn: String,
+    pub publisher: Option<String>,
+    pub published_on: Option<NaiveDate>,
+    pub format: BookFormat,
+    #[validate(range(min = 0, message = "Number of pages must be a number!"))]
+    pub pages: Option<i32>,
+}
+
+impl EditionRecord {
+    fn validated(self) -> Result<Self, String> {
+        self.validate().map_err(|err| err.to_string())?;
+        Ok(self)
+    }
+}
+
+#[derive(Debug, serde::Deserialize, Validate)]
+pub struct BookRecord {
+    #[validate(length(min = 1, message = "Title must not be empty!"))]
+    pub title: String,
+    /// Made from the title when missing.
+    #[validate(regex(path = *SLUG_REGEX, message = "Slug is not valid! It must only contain ASCII characters, numbers, and/or hyphens."))]
+    pub slug: Option<String>,
+    #[serde(default)]
+    pub summary: String,
+    #[validate(range(min = 0, message = "Number of pages must be a number!"))]
+    pub pages: Option<i32>,
+    /// The code of the book's language, which is added if it does not exist.
+    #[validate(length(min = 1, message = "Language is not valid!"))]
+    pub language: String,
+    /// The name a language that does not exist is added with, its code
+    /// by default.
+    pub language_name: Option<String>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    #[validate(nested)]
+    pub editions: Vec<EditionRecord>,
+}
+
+impl BookRecord {
+    fn into_record(self) -> Result<Record, String> {
+        self.normalized().map(Record::Book)
+    }
+
+    /// Makes the slug from the title when it is missing, and drops empty or
+    /// repeated authors and categories.
+    fn normalized(mut self) -> Result<Self, String> {
+        if self.slug.is_none() {
+            self.slug = Some(slugify(&self.title));
+        }
+        self.authors = names(self.authors);
+        self.categories = names(self.categories);
+        self.validate().map_err(|err| err.to_string())?;
+        Ok(self)
+    }
+}
+
+/// A row of a CSV file. Its edition is only imported when it has an ISBN.
+#[derive(serde::Deserialize)]
+struct CsvRow {
+    title: String,
+    slug: Option<String>,
+    summary: Option<String>,
+    pages: Option<i32>,
+    language: String,
+    language_name: Option<String>,
+    authors: Option<String>,
+    categories: Option<String>,
+    isbn: Option<String>,
+    publisher: Option<String>,
+    published_on: Option<NaiveDate>,
+    format: Option<BookFormat>,
+    edition_pages: Option<i32>,
+}
+
+impl TryFrom<CsvRow> for BookRecord {
+    type Error = String;
+
+    fn try_from(row: CsvRow) -> Result<Self, Self::Error> {
+        let split = |list: Option<String>| {
+            list.map(|list| list.split(';').map(str::to_owned).collect())
+                .unwrap_or_default()
+        };
+        let edition = match (row.isbn, row.format) {
+            (Some(isbn), Some(format)) => Some(EditionRecord {
+                isbn,
+                publisher: row.publisher,
+                published_on: row.published_on,
+                format,
+                pages: row.edition_pages,
+            }),
+            (Some(_), None) => return Err("An edition must have a format!".to_owned()),
+            (None, _) => None,
+        };
+        Ok(BookRecord {
+            title: row.title,
+            slug: row.slug,
+            summary: row.summary.unwrap_or_default(),
+            pages: row.pages,
+            language: row.language,
+            language_name: row.language_name,
+            authors: split(row.authors),
+            categories: split(row.categories),
+            editions: edition.into_iter().collect(),
+        })
+    }
+}
+
+pub enum Record {
+    Book(BookRecord),
+    /// An edition of the book with the slug `book`, which must exist.
+    Edition {
+        book: String,
+        edition: EditionRecord,
+    },
+    /// The name of the Open Library author `key`, which works refer to.
+    Author {
+        key: String,
+        name: String,
+    },
+    /// The Open Library work `key`, with the keys of its authors.
+    Work {
+        key: String,
+        authors: Vec<String>,
+        book: BookRecord,
+    },
+    /// An edition of the Open Library work `work`, which must be imported.
+    WorkEdition {
+        work: String,
+        edition: EditionRecord,
+    },
+}
+
+struct Row {
+    line: usize,
+    /// `None` when the row has nothing to import by itself.
+    record: Result<Option<Record>, String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct RowError {
+    pub file: String,
+    pub line: usize,
+    pub error: String,
+}
+
+/// What an import did. Rows that could not be imported are reported with
+/// their errors, and the others are imported regardless.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct Report {
+    /// Books that did not exist before.
+    pub created: u64,
+    /// Books that existed, and were updated.
+    pub updated: u64,
+    /// Editions that were added or updated.
+    pub editions: u64,
+    /// Rows with no book or edition to import by themselves, like Open
+    /// Library authors or editions without an ISBN.
+    pub skipped: u64,
+    pub errors: Vec<RowError>,
+}
+
+/// Turns `text` into a slug made of its ASCII letters and digits.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !matches!(c, '\'' | '’') && !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_owned()
+}
+
+/// Trims `names`, then drops those that are empty or repeated.
+fn names(names: Vec<String>) -> Vec<String> {
+    let mut trimmed = Vec::<String>::with_capacity(names.len());
+    for name in names {
+        let name = name.trim();
+        if !name.is_empty() && !trimmed.iter().any(|other| other == name) {
+            trimmed.push(name.to_owned());
+        }
+    }
+    trimmed
+}
+
+fn open(path: &Path) -> Result<Box<dyn Read + Send>, ImportError> {
+    let file = File::open(path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => ImportError::FileNotFound(path.display().to_string()),
+        _ => ImportError::IoError(err),
+    })?;
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        // Open Library dumps are made of several gzip members.
+        return Ok(Box::new(MultiGzDecoder::new(BufReader::new(file))));
+    }
+    Ok(Box::new(file))
+}
+
+/// The rows of an import file, read in order.
+enum Source {
+    Csv {
+        reader: csv::Reader<Box<dyn Read + Send>>,
+        headers: csv::StringRecord,
+    },
+    Lines {
+        lines: Lines<BufReader<Box<dyn Read + Send>>>,
+        line: usize,
+        format: Format,
+    },
+}
+
+impl Source {
+    fn open(path: &Path, format: Format) -> Result<Self, ImportError> {
+        let file = open(path)?;
+        match format {
+            Format::Csv => {
+                let mut reader = csv::ReaderBuilder::new()
+                    .trim(csv::Trim::All)
+                    .from_reader(file);
+                let headers = reader
+                    .headers()
+                    .map_err(|err| ImportError::Malformed(err.to_string()))?
+                    .clone();
+                Ok(Source::Csv { reader, headers })
+            }
+            Format::Jsonl | Format::OpenLibrary => Ok(Source::Lines {
+                lines: BufReader::new(file).lines(),
+                line: 0,
+                format,
+            }),
+        }
+    }
+
+    /// Reads up to `count` rows. Open Library rows are parsed with
+    /// `open_library`.
+    fn read(
+        &mut self,
+        count: usize,
+        open_library: &openlibrary::Parser,
+    ) -> Result<Vec<Row>, ImportError> {
+        let mut rows = Vec::with_capacity(count);
+        while rows.len() < count {
+            let row = match self {
+                Source::Csv { reader, headers } => {
+                    let mut record = csv::StringRecord::new();
+                    match reader.read_record(&mut record) {
+                        Ok(false) => break,
+                        Ok(true) => Row {
+                            line: record.position().map_or(0, |pos| pos.line() as usize),
+                            record: record
+                                .deserialize::<CsvRow>(Some(headers))
+                                .map_err(|err| err.to_string())
+                                .and_then(BookRecord::try_from)
+                                .and_then(BookRecord::into_record)
+                                .map(Some),
+                        },
+                        Err(err) if matches!(err.kind(), csv::ErrorKind::Io(_)) => {
+                            return Err(ImportError::IoError(err.into()));
+                        }
+                        Err(err) => Row {
+                            line: err.position().map_or(0, |pos| pos.line() as usize),
+                            record: Err(err.to_string()),
+                        },
+                    }
+                }
+                Source::Lines {
+                    lines,
+                    line,
+                    format,
+                } => {
+                    let Some(text) = lines.next() else {
+                        break;
+                    };
+                    *line += 1;
+                    let record = match text {
+                        Ok(text) if text.trim().is_empty() => continue,
+                        Ok(text) if *format == Format::OpenLibrary => open_library.parse(&text),
+                        Ok(text) => serde_json::from_str::<BookRecord>(&text)
+                            .map_err(|err| err.to_string())
+                            .and_then(BookRecord::into_record)
+                            .map(Some),
+                        Err(err) if err.kind() == ErrorKind::InvalidData => Err(err.to_string()),
+                        Err(err) => return Err(err.into()),
+                    };
+                    Row {
+                        line: *line,
+                        record,
+                    }
+                }
+            };
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+}
+
+/// Whether an import requested over HTTP is still running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, serde::Serialize)]
+#[sqlx(type_name = "istatus", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(serde::Serialize)]
+pub struct ImportJob {
+    pub id: i64,
+    pub status: ImportStatus,
+    /// What the import did, once it is done.
+    pub report: Option<serde_json::Value>,
+    /// Why the import failed, if it did.
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// How many books and editions a row imported.
+#[derive(Default)]
+struct Imported {
+    created: u64,
+    updated: u64,
+    editions: u64,
+    skipped: u64,
+}
+
+/// Opens every file of an import first, so that none is imported when
+/// another cannot be found.
+fn open_sources(paths: &[PathBuf], format: Format) -> Result<Vec<(String, Source)>, ImportError> {
+    paths
+        .iter()
+        .map(|path| Ok((path.display().to_string(), Source::open(path, format)?)))
+        .collect()
+}
+
+/// Imports the books in `paths`, in order. Every `batch` rows are imported
+/// in a single transaction, and importing one again updates what it
+/// imported before, adding the authors and categories it did not have.
+#[instrument(name = "Importing books", skip(pool))]
+pub async fn run(
+    pool: &PgPool,
+    paths: &[PathBuf],
+    format: Format,
+    language: &str,
+    batch: usize,
+) -> Result<Report, AppError> {
+    let sources = open_sources(paths, format)?;
+    import_sources(pool, sources, language, batch).await
+}
+
+/// Same as [`run`], except that the books are imported in the background,
+/// and the id of the job keeping track of the import is returned once the
+/// files are opened.
+#[instrument(name = "Starting an import", skip(pool))]
+pub async fn start(
+    pool: &PgPool,
+    user_id: i64,
+    paths: &[PathBuf],
+    format: Format,
+    language: &str,
+    batch: usize,
+) -> Result<i64, AppError> {
+    let sources = open_sources(paths, format)?;
+    let job_id = sqlx::query_scalar!(
+        "INSERT INTO import_jobs (user_id) VALUES ($1) RETURNING id",
+        &user_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let pool = pool.clone();
+    let language = language.to_owned();
+    tokio::spawn(async move {
+        // The import runs in a task of its own so that the job still fails
+        // when it panics, instead of staying running.
+        let import = tokio::spawn({
+            let pool = pool.clone();
+            async move { import_sources(&pool, sources, &language, batch).await }
+        });
+        let result = match import.await {
+            Ok(result) => result,
+            Err(err) => {
+                event!(Level::ERROR, error = %err, job_id, "import job panicked");
+                Err(ImportError::Panicked.into())
+            }
+        };
+        if let Err(err) = finish(&pool, job_id, result).await {
+            event!(Level::ERROR, error = %err, job_id, "failed to finish an import job");
+        }
+    });
+    Ok(job_id)
+}
+
+/// Records the outcome of the import job `job_id`.
+async fn finish(
+    pool: &PgPool,
+    job_id: i64,
+    result: Result<Report, AppError>,
+) -> Result<(), AppError> {
+    let (status, report, error) = match result {
+        Ok(report) => (
+            ImportStatus::Done,
+            Some(serde_json::to_value(report)?),
+            None,
+        ),
+        Err(err) => (ImportStatus::Failed, None, Some(err.to_string())),
+    };
+    sqlx::query!(
+        "UPDATE import_jobs SET status = $2, report = $3, error = $4, finished_at = NOW()
+        WHERE id = $1",
+        &job_id,
+        status as _,
+        report,
+        error,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Fails the import jobs left running, which were interrupted when the
+/// application stopped, and returns how many there were.
+pub async fn fail_interrupted(pool: &PgPool) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        "UPDATE import_jobs SET status = $1, error = $2, finished_at = NOW()
+        WHERE status = 'running'",
+        ImportStatus::Failed as _,
+        AppError::from(ImportError::Interrupted).to_string(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Returns the import job `job_id`.
+pub async fn read_job(pool: &PgPool, job_id: i64) -> Result<ImportJob, AppError> {
+    Ok(sqlx::query_as!(
+        ImportJob,
+        r#"SELECT
+            id,
+            status AS "status: ImportStatus",
+            report,
+            error,
+            created_at,
+            finished_at
+        FROM import_jobs
+        WHERE id = $1"#,
+        &job_id,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(ImportError::JobNotFound(job_id))?)
+}
+
+async fn import_sources(
+    pool: &PgPool,
+    sources: Vec<(String, Source)>,
+    language: &str,
+    batch: usize,
+) -> Result<Report, AppError> {
+    let mut report = Report::default();
+    let open_library = openlibrary::Parser::new(language);
+    for (file, mut source) in sources {
+        loop {
+            let rows = tokio::task::block_in_place(|| source.read(batch.max(1), &open_library))?;
+            if rows.is_empty() {
+                break;
+            }
+            import_batch(pool, &file, rows, &mut report).await?;
+        }
+    }
+
+    event!(
+        Level::INFO,
+        created = report.created,
+        updated = report.updated,
+        editions = report.editions,
+        errors = report.errors.len(),
+        "imported books"
+    );
+    Ok(report)
+}
+
+async fn import_batch(
+    pool: &PgPool,
+    file: &str,
+    rows: Vec<Row>,
+    report: &mut Report,
+) -> Result<(), AppError> {
+    let mut transaction = pool.begin().await?;
+    for Row { line, record } in rows {
+        let error = match record {
+            Ok(Some(record)) => {
+                // A row failing only rolls back to its savepoint, so the
+                // rest of the batch is still imported.
+                let mut savepoint = transaction.begin().await?;
+                match import_record(&mut savepoint, record).await {
+                    Ok(imported) => {
+                        savepoint.commit().await?;
+                        report.created += imported.created;
+                        report.updated += imported.updated;
+                        report.editions += imported.editions;
+                        report.skipped += imported.skipped;
+                        continue;
+                    }
+                    Err(err) => {
+                        savepoint.rollback().await?;
+                        err.to_string()
+                    }
+                }
+            }
+            Ok(None) => {
+                report.skipped += 1;
+                continue;
+            }
+            Err(error) => error,
+        };
+        report.errors.push(RowError {
+            file: file.to_owned(),
+            line,
+            error,
+        });
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+async fn import_record(conn: &mut PgConnection, record: Record) -> Result<Imported, AppError> {
+    match record {
+        Record::Book(book) => import_book(conn, &book).await.map(|(_, imported)| imported),
+        Record::Edition { book, edition } => {
+            let book_id = sqlx::query_scalar!("SELECT id FROM books WHERE name = $1", &book)
+                .fetch_optional(&mut *conn)
+                .await?
+                .ok_or(BooksError::BookNotFound(book))?;
+            import_edition(conn, book_id, &edition).await?;
+            Ok(Imported {
+                editions: 1,
+                ..Default::default()
+            })
+        }
+        Record::Author { key, name } => {
+            sqlx::query!(
+                "INSERT INTO open_library_authors (key, name) VALUES ($1, $2)
+                ON CONFLICT (key) DO UPDATE SET name = EXCLUDED.name",
+                &key,
+                &name,
+            )
+            .execute(&mut *conn)
+            .await?;
+            Ok(Imported {
+                skipped: 1,
+                ..Default::default()
+            })
+        }
+        Record::Work {
+            key,
+            authors,
+            mut book,
+        } => {
+            // Authors that were not imported are left out.
+            let authors = sqlx::query_scalar!(
+                "SELECT a.name
+                FROM unnest($1::TEXT[]) WITH ORDINALITY AS k(key, position)
+                JOIN open_library_authors a ON a.key = k.key
+                ORDER BY k.position",
+                &authors[..],
+            )
+            .fetch_all(&mut *conn)
+            .await?;
+            book.authors = names(authors);
+            let (book_id, imported) = import_book(conn, &book).await?;
+            sqlx::query!(
+                "INSERT INTO open_library_works (key, book_id) VALUES ($1, $2)
+                ON CONFLICT (key) DO UPDATE SET book_id = EXCLUDED.book_id",
+                &key,
+                &book_id,
+            )
+            .execute(&mut *conn)
+            .await?;
+            Ok(imported)
+        }
+        Record::WorkEdition { work, edition } => {
+            let book_id = sqlx::query_scalar!(
+                "SELECT book_id FROM open_library_works WHERE key = $1",
+                &work,
+            )
+            .fetch_optional(&mut *conn)
+            .await?
+            .ok_or(ImportError::WorkNotFound(work))?;
+            import_edition(conn, book_id, &edition).await?;
+            Ok(Imported {
+                editions: 1,
+                ..Default::default()
+            })
+        }
+    }
+}
+
+/// Upserts a book by its slug, and returns its id. Empty summaries and
+/// missing page counts leave those of an existing book as they are.
+async fn import_book(
+    conn: &mut PgConnection,
+    book: &BookRecord,
+) -> Result<(i64, Imported), AppError> {
+    let slug = book.slug.as_deref().ok_or(BooksError::Unexpected)?;
+    sqlx::query!(
+        "INSERT INTO book_languages (code, name) VALUES ($1, $2) ON CONFLICT (code) DO NOTHING",
+        &book.language,
+        book.language_name.as_deref().unwrap_or(&book.language),
+    )
+    .execute(&mut *conn)
+    .await?;
+    let upserted = sqlx::query!(
+        r#"INSERT INTO books (is_approved, title, name, pages, language, summary)
+        VALUES (TRUE, $1, $2, coalesce($3, 0), $4, $5)
+        ON CONFLICT (name) DO UPDATE SET
+            title = EXCLUDED.title,
+            pages = coalesce($3, books.pages),
+            language = EXCLUDED.language,
+            summary = CASE WHEN EXCLUDED.summary = '' THEN books.summary ELSE EXCLUDED.summary END
+        RETURNING id, (xmax = 0) AS "created!""#,
+        &book.title,
+        slug,
+        &book.pages as &_,
+        &book.language,
+        &book.summary,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    // Authors and categories are matched by name, and the oldest wins
+    // when several have the same one.
+    sqlx::query!(
+        "WITH input AS (
+            SELECT DISTINCT unnest($2::TEXT[]) AS name
+        ), inserted AS (
+            INSERT INTO book_authors (name)
+            SELECT i.name FROM input i
+            WHERE NOT EXISTS (SELECT 1 FROM book_authors ba WHERE ba.name = i.name)
+            RETURNING id
+        )
+        INSERT INTO book_to_author (book_id, author_id)
+        SELECT $1::BIGINT, id FROM inserted
+        UNION
+        SELECT $1, min(ba.id) FROM book_authors ba JOIN input i ON i.name = ba.name GROUP BY ba.name
+        ON CONFLICT DO NOTHING",
+        &upserted.id,
+        &book.authors[..],
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        "WITH input AS (
+            SELECT DISTINCT unnest($2::TEXT[]) AS name
+        ), inserted AS (
+            INSERT INTO book_categories (name)
+            SELECT i.name FROM input i
+            WHERE NOT EXISTS (SELECT 1 FROM book_categories bc WHERE bc.name = i.name)
+            RETURNING id
+        )
+        INSERT INTO book_to_category (book_id, category_id)
+        SELECT $1::BIGINT, id FROM inserted
+        UNION
+        SELECT $1, min(bc.id) FROM book_categories bc JOIN input i ON i.name = bc.name GROUP BY bc.name
+        ON CONFLICT DO NOTHING",
+        &upserted.id,
+        &book.categories[..],
+    )
+    .execute(&mut *conn)
+    .await?;
+    for edition in &book.editions {
+        import_edition(conn, upserted.id, edition).await?;
+    }
+    Ok((
+        upserted.id,
+        Imported {
+            created: upserted.created as u64,
+            updated: !upserted.created as u64,
+            editions: book.editions.len() as u64,
+            ..Default::default()
+        },
+    ))
+}
+
+/// Upserts an edition of the book `book_id` by its ISBN, which must not
+/// belong to another book.
+async fn import_edition(
+    conn: &mut PgConnection,
+    book_id: i64,
+    edition: &EditionRecord,
+) -> Result<(), AppError> {
+    let isbn = isbn::normalize(&edition.isbn).ok_or(BooksError::Unexpected)?;
+    sqlx::query_scalar!(
+        "INSERT INTO book_editions (book_id, isbn, publisher, published_on, format, pages)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (isbn) DO UPDATE SET
+            publisher = coalesce(EXCLUDED.publisher, book_editions.publisher),
+            published_on = coalesce(EXCLUDED.published_on, book_editions.published_on),
+            format = EXCLUDED.format,
+            pages = coalesce(EXCLUDED.pages, book_editions.pages)
+        WHERE book_editions.book_id = EXCLUDED.book_id
+        RETURNING id",
+        &book_id,
+        &isbn,
+        &edition.publisher as &_,
+        &edition.published_on as &_,
+        &edition.format as &_,
+        &edition.pages as &_,
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(BooksError::IsbnAlreadyExists(isbn))?;
+    // Open Library works have no page count, so they take the one of
+    // their first edition that has it.
+    if let Some(pages) = edition.pages {
+        sqlx::query!(
+            "UPDATE books SET pages = $2 WHERE id = $1 AND pages = 0",
+            &book_id,
+            &pages,
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use super::{slugify, BookRecord, EditionRecord, Record};
+use crate::{routes::books::BookFormat, utils::isbn};
+use chrono::NaiveDate;
+
+#[derive(serde::Deserialize)]
+struct Key {
+    key: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Author {
+    name: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct WorkAuthor {
+    author: Option<Key>,
+}
+
+/// Descriptions are either plain strings or typed values.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Text {
+    Plain(String),
+    Typed { value: String },
+}
+
+#[derive(serde::Deserialize)]
+struct Work {
+    title: Option<String>,
+    description: Option<Text>,
+    #[serde(default)]
+    subjects: Vec<String>,
+    #[serde(default)]
+    authors: Vec<WorkAuthor>,
+}
+
+#[derive(serde::Deserialize)]
+struct Edition {
+    #[serde(default)]
+    works: Vec<Key>,
+    #[serde(default)]
+    isbn_13: Vec<String>,
+    #[serde(default)]
+    isbn_10: Vec<String>,
+    #[serde(default)]
+    publishers: Vec<String>,
+    publish_date: Option<String>,
+    number_of_pages: Option<i32>,
+    physical_format: Option<String>,
+}
+
+/// Turns the rows of Open Library dumps into records. Works refer to their
+/// authors, and editions to their work, by key, which are only resolved
+/// when they are imported. So authors must be imported before works and
+/// works before editions, be it earlier in the same dump or in another
+/// import.
+pub struct Parser {
+    /// The language works are imported with, as they do not have one.
+    language: String,
+}
+
+impl Parser {
+    pub fn new(language: &str) -> Self {
+        Self {
+            language: language.to_owned(),
+        }
+    }
+
+    /// Parses a row made of the type, key, revision and last modification
+    /// time of a record, then the record itself as JSON, separated by tabs.
+    pub fn parse(&self, row: &str) -> Result<Option<Record>, String> {
+        let mut columns = row.splitn(5, '\t');
+        let (Some(kind), Some(key), Some(_), Some(_), Some(json)) = (
+            columns.next(),
+            columns.next(),
+            columns.next(),
+            columns.next(),
+            columns.next(),
+        ) else {
+            return Err("Row must have 5 columns separated by tabs!".to_owned());
+        };
+        match kind {
+            "/type/author" => {
+                let author: Author = serde_json::from_str(json).map_err(|err| err.to_string())?;
+                Ok(author.name.map(|name| Record::Author {
+                    key: key.to_owned(),
+                    name,
+                }))
+            }
+            "/type/work" => {
+                let work = serde_json::from_str(json).map_err(|err| err.to_string())?;
+                self.parse_work(key, work).map(Some)
+            }
+            "/type/edition" => {
+                let edition = serde_json::from_str(json).map_err(|err| err.to_string())?;
+                self.parse_edition(edition)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_work(&self, key: &str, work: Work) -> Result<Record, String> {
+        let title = work.title.unwrap_or_default();
+        // Titles are not unique, so the ID of the work, like `OL45804W`,
+        // is kept in its slug.
+        let id = key.rsplit('/').next().unwrap_or(key).to_ascii_lowercase();
+        let slug = match slugify(&title) {
+            slug if slug.is_empty() => id,
+            slug => format!("{slug}-{id}"),
+        };
+        let authors = work
+            .authors
+            .into_iter()
+            .filter_map(|author| Some(author.author?.key))
+            .collect();
+        let book = BookRecord {
+            title,
+            slug: Some(slug),
+            summary: match work.description {
+                Some(Text::Plain(value) | Text::Typed { value }) => value,
+                None => String::new(),
+            },
+            pages: None,
+            language: self.language.clone(),
+            language_name: None,
+            authors: Vec::new(),
+            categories: work.subjects,
+            editions: Vec::new(),
+        }
+        .normalized()?;
+        Ok(Record::Work {
+            key: key.to_owned(),
+            authors,
+            book,
+        })
+    }
+
+    fn parse_edition(&self, edition: Edition) -> Result<Option<Record>, String> {
+        let Some(work) = edition.works.first() else {
+            return Ok(None);
+        };
+        // Dumps have malformed ISBNs, which are only reported when no
+        // other ISBN of the edition is valid.
+        let isbns = edition
+            .isbn_13
+            .into_iter()
+            .chain(edition.isbn_10)
+            .collect::<Vec<_>>();
+        let Some(isbn) = isbns
+            .iter()
+            .find(|isbn| isbn::normalize(isbn).is_some())
+            .or(isbns.first())
+        else {
+            return Ok(None);
+        };
+        let edition = EditionRecord {
+            isbn: isbn.clone(),
+            publisher: edition.publishers.into_iter().next(),
+            published_on: edition.publish_date.as_deref().and_then(parse_date),
+            format: edition
+                .physical_format
+                .as_deref()
+                .map_or(BookFormat::Paperback, parse_format),
+            pages: edition.number_of_pages,
+        }
+        .validated()?;
+        Ok(Some(Record::WorkEdition {
+            work: work.key.clone(),
+            edition,
+        }))
+    }
+}
+
+/// Publication dates are free text, and only full dates are kept.
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    ["%B %d, %Y", "%b %d, %Y", "%Y-%m-%d", "%d %B %Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date.trim(), format).ok())
+}
+
+/// Physical formats are free text, like `Hardcover`, `Mass Market
+/// Paperback` or `Audio CD`. Those that are not recognized are paperbacks.
+fn parse_format(format: &str) -> BookFormat {
+    let format = format.to_lowercase();
+    if format.contains("hard") {
+        BookFormat::Hardcover
+    } else if format.contains("audio") {
+        BookFormat::Audiobook
+    } else if ["ebook", "e-book", "electronic", "kindle", "epub"]
+        .iter()
+        .any(|kind| format.contains(kind))
+    {
+        BookFormat::Ebook
+    } else {
+        BookFormat::Paperback
+    }
+}
@@ -0,0 +1,66 @@
+use crate::utils::{errors::AppError, markdown};
+use sqlx::PgPool;
+use tracing::{event, instrument, Level};
+
+const BATCH_SIZE: i64 = 100;
+
+/// Renders the content of posts and comments that were
+/// created before `content_html` was introduced.
+#[instrument(name = "Rendering legacy content", skip(pool))]
+pub async fn render_missing(pool: &PgPool) -> Result<(), AppError> {
+    let mut rendered = 0;
+    loop {
+        let posts = sqlx::query!(
+            "SELECT id, content FROM posts WHERE content_html IS NULL LIMIT $1",
+            BATCH_SIZE
+        )
+        .fetch_all(pool)
+        .await?;
+        if posts.is_empty() {
+            break;
+        }
+        let ids = posts.iter().map(|post| post.id).collect::<Vec<_>>();
+        let htmls = posts
+            .iter()
+            .map(|post| markdown::render(&post.content))
+            .collect::<Vec<_>>();
+        sqlx::query!(
+            "UPDATE posts p SET content_html = r.html
+            FROM UNNEST($1::BIGINT[], $2::TEXT[]) AS r(id, html)
+            WHERE p.id = r.id",
+            &ids,
+            &htmls
+        )
+        .execute(pool)
+        .await?;
+        rendered += ids.len();
+    }
+    loop {
+        let comments = sqlx::query!(
+            "SELECT id, content FROM comments WHERE content_html IS NULL LIMIT $1",
+            BATCH_SIZE
+        )
+        .fetch_all(pool)
+        .await?;
+        if comments.is_empty() {
+            break;
+        }
+        let ids = comments.iter().map(|comment| comment.id).collect::<Vec<_>>();
+        let htmls = comments
+            .iter()
+            .map(|comment| markdown::render(&comment.content))
+            .collect::<Vec<_>>();
+        sqlx::query!(
+            "UPDATE comments c SET content_html = r.html
+            FROM UNNEST($1::BIGINT[], $2::TEXT[]) AS r(id, html)
+            WHERE c.id = r.id",
+            &ids,
+            &htmls
+        )
+        .execute(pool)
+        .await?;
+        rendered += ids.len();
+    }
+    event!(Level::INFO, rendered, "rendered legacy content");
+    Ok(())
+}
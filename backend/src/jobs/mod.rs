@@ -0,0 +1,66 @@
+use crate::{app::AppState, settings::SETTINGS};
+use std::time::Duration;
+use tracing::{event, Level};
+
+pub mod gc;
+pub mod import;
+pub mod markdown;
+pub mod recommendations;
+pub mod trending;
+
+/// Spawns the jobs that run in the background for the
+/// lifetime of the application, once the import jobs a previous run left
+/// running are failed.
+pub async fn spawn(state: &AppState) {
+    match import::fail_interrupted(&state.pool).await {
+        Ok(0) => {}
+        Ok(count) => event!(Level::WARN, count, "failed interrupted import jobs"),
+        Err(err) => event!(Level::ERROR, error = %err, "failed to fail interrupted import jobs"),
+    }
+
+    let pool = state.pool.clone();
+    tokio::spawn(async move {
+        if let Err(err) = markdown::render_missing(&pool).await {
+            event!(Level::ERROR, error = %err, "failed to render legacy content");
+        }
+    });
+
+    let pool = state.pool.clone();
+    let storage = state.storage.clone();
+    tokio::spawn(async move {
+        let settings = &SETTINGS.storage.gc;
+        let mut interval = tokio::time::interval(Duration::from_secs(settings.interval));
+        loop {
+            interval.tick().await;
+            let grace = Duration::from_secs(settings.grace);
+            if let Err(err) = gc::collect(&pool, &*storage, grace, settings.dry).await {
+                event!(Level::ERROR, error = %err, "failed to collect orphaned files");
+            }
+        }
+    });
+
+    let pool = state.pool.clone();
+    let redis_client = state.redis_client.clone();
+    tokio::spawn(async move {
+        let settings = &SETTINGS.recommendations;
+        let mut interval = tokio::time::interval(Duration::from_secs(settings.interval));
+        loop {
+            interval.tick().await;
+            if let Err(err) = recommendations::refresh(&pool, &redis_client, settings.limit).await {
+                event!(Level::ERROR, error = %err, "failed to refresh recommendations");
+            }
+        }
+    });
+
+    let pool = state.pool.clone();
+    tokio::spawn(async move {
+        let settings = &SETTINGS.trending;
+        let mut interval = tokio::time::interval(Duration::from_secs(settings.interval));
+        loop {
+            interval.tick().await;
+            if let Err(err) = trending::refresh(&pool, settings.window, settings.half_life).await {
+                event!(Level::ERROR, error = %err, "failed to refresh trending");
+            }
+        }
+    });
+}
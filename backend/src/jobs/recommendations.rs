@@ -0,0 +1,49 @@
+use crate::utils::errors::AppError;
+use redis::Commands;
+use sqlx::PgPool;
+use tracing::{event, instrument, Level};
+
+const CACHE_PREFIX: &str = "recommendations";
+
+/// What recommendations are computed for.
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    Book(i64),
+    User(i64),
+}
+
+/// Returns the Redis key the recommendations for `target` are cached under.
+pub fn cache_key(target: Target) -> String {
+    match target {
+        Target::Book(id) => format!("{CACHE_PREFIX}:book:{id}"),
+        Target::User(id) => format!("{CACHE_PREFIX}:user:{id}"),
+    }
+}
+
+/// Recomputes the recommendations for every book and user, then drops
+/// those cached from the previous run.
+#[instrument(name = "Refreshing recommendations", skip(pool, redis_client))]
+pub async fn refresh(
+    pool: &PgPool,
+    redis_client: &redis::Client,
+    limit: i32,
+) -> Result<(), AppError> {
+    sqlx::query!("SELECT refresh_recommendations($1)", limit)
+        .execute(pool)
+        .await?;
+
+    let mut redis_con = redis_client.get_connection()?;
+    let keys = redis_con
+        .scan_match::<_, String>(format!("{CACHE_PREFIX}:*"))?
+        .collect::<Vec<_>>();
+    if !keys.is_empty() {
+        let _: () = redis_con.del(&keys)?;
+    }
+
+    event!(
+        Level::INFO,
+        evicted = keys.len(),
+        "refreshed recommendations"
+    );
+    Ok(())
+}
@@ -0,0 +1,16 @@
+use crate::utils::errors::AppError;
+use sqlx::PgPool;
+use tracing::{event, instrument, Level};
+
+/// Recomputes the scores of trending books and reviews from the activity of
+/// the last `window` seconds, which counts half as much every `half_life`
+/// seconds. Both are replaced in a single transaction, so they can still be
+/// read in the meantime.
+#[instrument(name = "Refreshing trending", skip(pool))]
+pub async fn refresh(pool: &PgPool, window: i64, half_life: i64) -> Result<(), AppError> {
+    sqlx::query!("SELECT refresh_trending($1, $2)", window, half_life)
+        .execute(pool)
+        .await?;
+    event!(Level::INFO, "refreshed trending");
+    Ok(())
+}
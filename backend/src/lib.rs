@@ -1,7 +1,12 @@
 pub mod app;
+#[cfg(feature = "hdfs")]
+pub mod bindgen;
+#[cfg(feature = "hdfs")]
+pub mod hdfs;
+pub mod jobs;
 pub mod routes;
+pub mod scanner;
 pub mod settings;
+pub mod storage;
 pub mod telemetry;
-pub mod utils;
-// pub mod bindgen;
-// pub mod hdfs;
\ No newline at end of file
+pub mod utils;
\ No newline at end of file
@@ -8,13 +8,13 @@ use crate::{
         errors::AppError,
         response::{created, response, SuccessResponse},
         structs::{AppForm, AppImage, AppJson, AppMultipart},
-        uploads::upload_file,
+        uploads::upload_or_reference,
     },
 };
 use axum::{
     async_trait,
     body::Bytes,
-    extract::{FromRequestParts, Query, State},
+    extract::{FromRef, FromRequestParts, Query, State},
     http::{header, request::Parts, HeaderValue, StatusCode},
     response::Response,
     RequestPartsExt,
@@ -26,6 +26,7 @@ use axum_extra::{
     TypedHeader,
 };
 use axum_typed_multipart::{FieldData, TryFromMultipart};
+use chrono::{DateTime, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use rand::{distributions::Alphanumeric, Rng};
 use redis::Commands;
@@ -42,6 +43,12 @@ pub enum AuthError {
     Unexpected,
     #[error("an token was already used when it was requested again")]
     TokenUsed,
+    #[error("this user has been banned")]
+    Banned,
+    #[error("this user has been suspended until {0}")]
+    Suspended(DateTime<Utc>),
+    #[error("this user is not allowed to perform this action")]
+    Forbidden,
 }
 
 #[derive(Clone, Debug, PartialEq, PartialOrd, sqlx::Type, serde::Deserialize, serde::Serialize)]
@@ -110,9 +117,30 @@ impl UserClaims {
     }
 }
 
+/// Rejects users that have been banned or are currently suspended.
+pub async fn ensure_not_restricted(pool: &sqlx::PgPool, uid: i64) -> Result<(), AppError> {
+    let user = sqlx::query!(
+        "SELECT is_banned, suspended_until FROM users WHERE id = $1",
+        &uid
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AuthError::Invalid)?;
+    if user.is_banned {
+        return Err(AuthError::Banned.into());
+    }
+    if let Some(until) = user.suspended_until {
+        if until > Utc::now() {
+            return Err(AuthError::Suspended(until).into());
+        }
+    }
+    Ok(())
+}
+
 #[async_trait]
 impl<S> FromRequestParts<S> for UserClaims
 where
+    AppState: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = AppError;
@@ -121,7 +149,39 @@ where
         // Extract the token from the authorization header
         let OptionalUserClaims(claims) =
             OptionalUserClaims::from_request_parts(parts, state).await?;
-        Ok(claims.ok_or_else(|| AuthError::Invalid)?)
+        let claims = claims.ok_or(AuthError::Invalid)?;
+        // Tokens stay valid for a while, so restrictions that were put in place
+        // after they were issued have to be checked on every request.
+        let AppState { pool, .. } = AppState::from_ref(state);
+        ensure_not_restricted(&pool, claims.sub).await?;
+        Ok(claims)
+    }
+}
+
+/// Used for endpoints that can only be accessed by administrators.
+pub struct AdminClaims(pub UserClaims);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminClaims
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claims = UserClaims::from_request_parts(parts, state).await?;
+        let AppState { pool, .. } = AppState::from_ref(state);
+        let role = sqlx::query_scalar!(
+            r#"SELECT role AS "role: UserRole" FROM users WHERE id = $1"#,
+            &claims.sub
+        )
+        .fetch_one(&pool)
+        .await?;
+        if role != UserRole::Admin {
+            return Err(AuthError::Forbidden.into());
+        }
+        Ok(AdminClaims(claims))
     }
 }
 
@@ -413,12 +473,22 @@ pub struct RegisterPayload {
 
 #[instrument(
     name = "Registering a new user",
-    skip(pool, redis_client, email, password, username, picture)
+    skip(
+        pool,
+        storage,
+        scanner,
+        redis_client,
+        email,
+        password,
+        username,
+        picture
+    )
 )]
 pub async fn register(
     State(AppState {
         pool,
-        s3,
+        storage,
+        scanner,
         redis_client,
         ..
     }): State<AppState>,
@@ -451,7 +521,15 @@ pub async fn register(
             return Err(AppError::from(err));
         }
     };
-    let picture_id = upload_file(&mut transaction, &s3, uid, None, picture).await?;
+    let picture_id = upload_or_reference(
+        &mut transaction,
+        &*storage,
+        &*scanner,
+        uid,
+        Some(picture),
+        None,
+    )
+    .await?;
     sqlx::query!(
         "UPDATE users SET picture_id = $1 WHERE id = $2",
         &picture_id,
@@ -494,7 +572,8 @@ pub async fn login(
 ) -> Result<Response, AppError> {
     let mut transaction = pool.begin().await?;
     let user = sqlx::query!(
-        "SELECT id, name, email, password, is_verified FROM users WHERE name = $1",
+        "SELECT id, name, email, password, is_verified, is_banned, suspended_until
+        FROM users WHERE name = $1",
         &username
     )
     .fetch_one(&mut *transaction)
@@ -509,6 +588,14 @@ pub async fn login(
     if !utils::password::verify(password_hash, password)? {
         return Err(AppError::from(AuthError::Invalid));
     }
+    if user.is_banned {
+        return Err(AppError::from(AuthError::Banned));
+    }
+    if let Some(until) = user.suspended_until {
+        if until > Utc::now() {
+            return Err(AppError::from(AuthError::Suspended(until)));
+        }
+    }
     let now = chrono::Local::now();
     let id_ttl = chrono::Duration::seconds(SETTINGS.auth.access.exp);
     let id_claims = UserClaims::new(
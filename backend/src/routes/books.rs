@@ -1,23 +1,26 @@
 use super::{
-    auth::{OptionalUserClaims, UserClaims},
+    auth::{AdminClaims, OptionalUserClaims, UserClaims},
     posts::Post,
 };
 use crate::{
     app::AppState,
+    jobs::import,
     settings::SETTINGS,
     utils::{
         constants::SLUG_REGEX,
         errors::AppError,
+        isbn,
         response::{created, response},
         structs::{AppForm, AppImage, AppJson, AppMultipart, AppQuery},
-        uploads::upload_file,
+        uploads::{upload_or_reference, UploadsError},
+        validators::{isbn_is_valid, path_is_valid},
     },
 };
 use axum::{
     body::Bytes,
     extract::{Path, State},
     http::StatusCode,
-    response::Response,
+    response::{IntoResponse, Response},
 };
 use axum_typed_multipart::{FieldData, TryFromMultipart};
 use chrono::NaiveDate;
@@ -35,6 +38,12 @@ pub struct BookReactionMetadata {
     total: i64,
     like: i64,
     dislike: i64,
+    /// How many reviews have a rating.
+    rated: i64,
+    /// The average rating, out of 5 stars.
+    average: Option<f64>,
+    /// How many ratings there are of each half star, from 1 to 5 stars.
+    histogram: Vec<i64>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -47,6 +56,16 @@ pub enum BooksError {
     BookNotFound(String),
     #[error("user is already tracking book {0}")]
     AlreadyTracking(String),
+    #[error("duplicate ISBN {0}")]
+    IsbnAlreadyExists(String),
+    #[error("edition {0} cannot be found")]
+    EditionNotFound(i64),
+    #[error("duplicate series {0}")]
+    SeriesAlreadyExists(String),
+    #[error("series {0} cannot be found")]
+    SeriesNotFound(i64),
+    #[error("position {0} of the series is already taken")]
+    SeriesPositionTaken(i32),
     #[error("this error is not expected")]
     Unexpected,
 }
@@ -68,14 +87,23 @@ pub struct CreatePayload {
     #[validate(length(min = 1, message = "Book must has at least one category!"))]
     categories: Vec<i64>,
     #[form_data(limit = "2000000")]
-    cover_image: FieldData<Bytes>,
+    cover_image: Option<FieldData<Bytes>>,
+    /// A previously uploaded file to use instead of `cover_image`.
+    cover_id: Option<i64>,
     #[form_data(limit = "2000000")]
-    spine_image: FieldData<Bytes>,
+    spine_image: Option<FieldData<Bytes>>,
+    /// A previously uploaded file to use instead of `spine_image`.
+    spine_id: Option<i64>,
 }
 
-#[instrument(name = "Creating a new book...", skip(pool, s3, claims, cover_image, spine_image), fields(uid = %claims.sub))]
+#[instrument(name = "Creating a new book...", skip(pool, storage, scanner, claims, cover_image, spine_image), fields(uid = %claims.sub))]
 pub async fn create(
-    State(AppState { pool, s3, .. }): State<AppState>,
+    State(AppState {
+        pool,
+        storage,
+        scanner,
+        ..
+    }): State<AppState>,
     claims: UserClaims,
     AppMultipart(CreatePayload {
         title,
@@ -86,12 +114,30 @@ pub async fn create(
         authors,
         categories,
         cover_image,
+        cover_id,
         spine_image,
+        spine_id,
     }): AppMultipart<CreatePayload>,
 ) -> Result<Response, AppError> {
     let mut transaction = pool.begin().await?;
-    let cover_id = upload_file(&mut transaction, &s3, claims.sub, None, cover_image).await?;
-    let spine_id = upload_file(&mut transaction, &s3, claims.sub, None, spine_image).await?;
+    let cover_id = upload_or_reference(
+        &mut transaction,
+        &*storage,
+        &*scanner,
+        claims.sub,
+        cover_image,
+        cover_id,
+    )
+    .await?;
+    let spine_id = upload_or_reference(
+        &mut transaction,
+        &*storage,
+        &*scanner,
+        claims.sub,
+        spine_image,
+        spine_id,
+    )
+    .await?;
     let bid: i64 = sqlx::query_scalar!(
         "INSERT INTO books (is_approved, title, name, pages, language, summary, cover_id, spine_id)
         VALUES (FALSE, $1, $2, $3, $4, $5, $6, $7)
@@ -110,6 +156,7 @@ pub async fn create(
         sqlx::Error::Database(ref db_err) => match db_err.constraint() {
             Some("books_name_key") => AppError::from(BooksError::SlugAlreadyExists(slug.clone())),
             Some("books_language_fkey") => AppError::from(BooksError::LanguageInvalid(language)),
+            Some("files_status_ready") => AppError::from(UploadsError::FileNotUploaded),
             _ => AppError::from(err),
         },
         err => AppError::from(err),
@@ -150,24 +197,119 @@ pub struct Book {
     pub name: String,
     pub summary: String,
     pub language: String,
-    pub cover_image: sqlx::types::Json<AppImage>,
-    pub spine_image: sqlx::types::Json<AppImage>,
+    pub cover_image: Option<sqlx::types::Json<AppImage>>,
+    pub spine_image: Option<sqlx::types::Json<AppImage>>,
     pub authors: sqlx::types::Json<Vec<BookAuthor>>,
     pub categories: sqlx::types::Json<Vec<BookCategory>>,
     pub reactions: Option<sqlx::types::Json<BookReactionMetadata>>,
     pub reviews: Option<sqlx::types::Json<Vec<Post>>>,
 }
 
+/// How a list of categories or authors is matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    /// Books must have at least one of them.
+    Any,
+    /// Books must have all of them.
+    All,
+}
+
+/// What books are ranked by, after how well they match the query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookRanking {
+    /// The share of reviews that liked the book, and how many there are.
+    Reactions,
+    /// The Bayesian average of the book's ratings.
+    Rating,
+}
+
 #[derive(serde::Deserialize, Validate)]
+#[validate(schema(function = "validate_read_query"))]
 pub struct ReadQuery {
     /// Whether reviews should be included with books.
     include_reviews: Option<bool>,
     #[validate(length(min = 1, message = "Query is not valid!"))]
     q: Option<String>,
+    /// The code of the language `q` is written in. Otherwise, `q` is
+    /// interpreted in the language of each book.
+    #[validate(length(min = 1, message = "Language is not valid!"))]
+    lang: Option<String>,
+    /// The codes of the languages books must be written in.
+    #[validate(length(min = 1, message = "No languages are specified!"))]
+    languages: Option<Vec<String>>,
+    #[validate(range(min = 0, message = "Number of pages must be a number!"))]
+    min_pages: Option<i32>,
+    #[validate(range(min = 0, message = "Number of pages must be a number!"))]
+    max_pages: Option<i32>,
+    /// The lowest share of reviews that liked the book, between 0 and 1.
+    #[validate(range(min = 0.0, max = 1.0, message = "Rating must be between 0 and 1!"))]
+    min_rating: Option<f64>,
+    has_reviews: Option<bool>,
     #[validate(length(min = 1, message = "No categories are specified!"))]
     categories: Option<Vec<i64>>,
+    /// Defaults to `all`.
+    categories_mode: Option<MatchMode>,
     #[validate(length(min = 1, message = "No authors are specified!"))]
     authors: Option<Vec<i64>>,
+    /// Defaults to `all`.
+    authors_mode: Option<MatchMode>,
+    /// Defaults to `reactions`.
+    ranking: Option<BookRanking>,
+}
+
+fn validate_read_query(query: &ReadQuery) -> Result<(), ValidationError> {
+    if let (Some(min_pages), Some(max_pages)) = (query.min_pages, query.max_pages) {
+        if min_pages > max_pages {
+            return Err(ValidationError::new(
+                "The minimum number of pages cannot exceed the maximum.",
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct LanguageFacet {
+    code: String,
+    name: String,
+    count: i64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct NamedFacet {
+    id: i64,
+    name: String,
+    count: i64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct RangeFacet<T> {
+    min: T,
+    /// Unbounded when not set.
+    max: Option<T>,
+    count: i64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ReviewsFacet {
+    with: i64,
+    without: i64,
+}
+/// How many books match each value of a filter. Every dimension is counted
+/// over the books passing all the other filters, so that the counts tell how
+/// the results would change by picking another value.
+#[derive(serde::Serialize)]
+pub struct BookFacets {
+    languages: sqlx::types::Json<Vec<LanguageFacet>>,
+    categories: sqlx::types::Json<Vec<NamedFacet>>,
+    authors: sqlx::types::Json<Vec<NamedFacet>>,
+    pages: sqlx::types::Json<Vec<RangeFacet<i32>>>,
+    ratings: sqlx::types::Json<Vec<RangeFacet<f64>>>,
+    reviews: sqlx::types::Json<ReviewsFacet>,
+}
+#[derive(serde::Serialize)]
+pub struct ReadResponse {
+    books: Vec<Book>,
+    facets: BookFacets,
 }
 
 #[instrument(name = "Reading books...", skip(pool, claims))]
@@ -177,14 +319,32 @@ pub async fn read(
     AppQuery(ReadQuery {
         include_reviews,
         q,
+        lang,
+        languages,
+        min_pages,
+        max_pages,
+        min_rating,
+        has_reviews,
         categories,
+        categories_mode,
         authors,
+        authors_mode,
+        ranking,
     }): AppQuery<ReadQuery>,
 ) -> Result<Response, AppError> {
     let uid = claims.as_ref().map(|claims| claims.sub);
     let mut transaction = pool.begin().await?;
+    if let Some(lang) = lang.as_ref() {
+        sqlx::query!("SELECT code FROM book_languages WHERE code = $1", lang)
+            .fetch_optional(&mut *transaction)
+            .await?
+            .ok_or_else(|| BooksError::LanguageInvalid(lang.clone()))?;
+    }
     let include_reviews = include_reviews.unwrap_or(true);
-    let books_list = sqlx::query_as!(
+    let all_categories = categories_mode != Some(MatchMode::Any);
+    let all_authors = authors_mode != Some(MatchMode::Any);
+    let bayesian = ranking == Some(BookRanking::Rating);
+    let books = sqlx::query_as!(
         Book,
         r#"
         SELECT
@@ -192,8 +352,8 @@ pub async fn read(
             b.name AS "name!",
             b.summary AS "summary!",
             b.lang AS "language!",
-            b.cover_image AS "cover_image!: _",
-            b.spine_image AS "spine_image!: _",
+            b.cover_image AS "cover_image?: _",
+            b.spine_image AS "spine_image?: _",
             b.authors AS "authors!: _",
             b.categories AS "categories!: _",
             b.reactions AS "reactions?: _",
@@ -201,7 +361,22 @@ pub async fn read(
                 WHEN TRUE THEN coalesce(jsonb_agg(rv) FILTER (WHERE rv.id IS NOT NULL), '[]'::JSONB)
                 ELSE NULL
             END AS "reviews?: _"
-        FROM books_view b
+        FROM filter_books(
+            query => $2,
+            query_lang => $6,
+            languages => $7,
+            min_pages => $8,
+            max_pages => $9,
+            min_rating => $10,
+            has_reviews => $11,
+            categories => $3,
+            all_categories => $12,
+            authors => $4,
+            all_authors => $13,
+            bayesian => $14
+        ) fb
+        JOIN books_view b
+        ON b.id = fb.id
         LEFT JOIN LATERAL (
             SELECT *
             FROM fetch_posts(request_uid => $1) rv
@@ -210,49 +385,180 @@ pub async fn read(
             LIMIT 5
             OFFSET 0
         ) rv ON TRUE
-        LEFT JOIN LATERAL (
-            SELECT books_boost_rating(brt) AS rating
-            FROM book_reactions_tally brt
-            WHERE brt.book_id = b.id
-        ) bbr ON TRUE
-        JOIN LATERAL (
-            SELECT CASE
-                WHEN $2::TEXT IS NOT NULL THEN websearch_to_tsquery(coalesce($2, ''))
-                ELSE NULL
-            END AS query
-        ) query ON TRUE
-        JOIN LATERAL (
-            SELECT CASE
-                WHEN query.query IS NULL THEN 0
-                ELSE ts_rank(b.text_search, query.query)
-            END + CASE
-                WHEN bbr IS NULL THEN 0
-                ELSE bbr.rating
-            END AS rank
-        ) rank ON TRUE
-        WHERE CASE
-            WHEN query.query IS NULL THEN TRUE
-            ELSE b.text_search @@ query.query
-        END AND CASE
-            WHEN $3::BIGINT[] IS NULL THEN TRUE
-            ELSE b.categories_raw @> $3
-        END AND CASE
-            WHEN $4::BIGINT[] IS NULL THEN TRUE
-            ELSE b.authors_raw @> $4
-        END
+        WHERE fb.language_ok AND fb.pages_ok AND fb.rating_ok
+        AND fb.reviews_ok AND fb.categories_ok AND fb.authors_ok
         GROUP BY b.title, b.name, b.summary, b.lang, b.cover_image,
-        b.spine_image, b.authors, b.categories, b.reactions, rank.rank
-        ORDER BY rank.rank DESC"#,
+        b.spine_image, b.authors, b.categories, b.reactions, fb.rank
+        ORDER BY fb.rank DESC"#,
         &uid as &_,
         &q as &_,
         &categories as &_,
         &authors as &_,
         &include_reviews,
+        &lang as &_,
+        &languages as &_,
+        &min_pages as &_,
+        &max_pages as &_,
+        &min_rating as &_,
+        &has_reviews as &_,
+        &all_categories,
+        &all_authors,
+        &bayesian,
     )
     .fetch_all(&mut *transaction)
     .await?;
+    let facets = sqlx::query_as!(
+        BookFacets,
+        r#"
+        WITH fb AS (
+            SELECT * FROM filter_books(
+                query => $1,
+                query_lang => $2,
+                languages => $3,
+                min_pages => $4,
+                max_pages => $5,
+                min_rating => $6,
+                has_reviews => $7,
+                categories => $8,
+                all_categories => $9,
+                authors => $10,
+                all_authors => $11,
+                bayesian => FALSE
+            )
+        )
+        SELECT
+            (
+                SELECT coalesce(jsonb_agg(l ORDER BY l.count DESC, l.name), '[]'::JSONB)
+                FROM (
+                    SELECT bl.code, bl.name, count(*) AS count
+                    FROM fb
+                    JOIN book_languages bl ON bl.code = fb.language
+                    WHERE fb.pages_ok AND fb.rating_ok AND fb.reviews_ok
+                    AND fb.categories_ok AND fb.authors_ok
+                    GROUP BY bl.code, bl.name
+                ) l
+            ) AS "languages!: _",
+            (
+                SELECT coalesce(jsonb_agg(c ORDER BY c.count DESC, c.name), '[]'::JSONB)
+                FROM (
+                    SELECT bc.id, bc.name, count(*) AS count
+                    FROM fb
+                    JOIN book_to_category btc ON btc.book_id = fb.id
+                    JOIN book_categories bc ON bc.id = btc.category_id
+                    WHERE fb.language_ok AND fb.pages_ok AND fb.rating_ok
+                    AND fb.reviews_ok AND fb.authors_ok
+                    GROUP BY bc.id, bc.name
+                ) c
+            ) AS "categories!: _",
+            (
+                SELECT coalesce(jsonb_agg(a ORDER BY a.count DESC, a.name), '[]'::JSONB)
+                FROM (
+                    SELECT ba.id, ba.name, count(*) AS count
+                    FROM fb
+                    JOIN book_to_author bta ON bta.book_id = fb.id
+                    JOIN book_authors ba ON ba.id = bta.author_id
+                    WHERE fb.language_ok AND fb.pages_ok AND fb.rating_ok
+                    AND fb.reviews_ok AND fb.categories_ok
+                    GROUP BY ba.id, ba.name
+                ) a
+            ) AS "authors!: _",
+            (
+                SELECT jsonb_agg(p ORDER BY p.min)
+                FROM (
+                    SELECT r.min, r.max, count(fb.id) AS count
+                    FROM (VALUES (0, 99), (100, 299), (300, 499), (500, NULL)) r(min, max)
+                    LEFT JOIN fb
+                    ON fb.pages >= r.min AND (r.max IS NULL OR fb.pages <= r.max)
+                    AND fb.language_ok AND fb.rating_ok AND fb.reviews_ok
+                    AND fb.categories_ok AND fb.authors_ok
+                    GROUP BY r.min, r.max
+                ) p
+            ) AS "pages!: _",
+            (
+                SELECT jsonb_agg(r ORDER BY r.min)
+                FROM (
+                    SELECT r.min, NULL::FLOAT8 AS max, count(fb.id) AS count
+                    FROM (VALUES (0.5::FLOAT8), (0.7), (0.9)) r(min)
+                    LEFT JOIN fb
+                    ON fb.rating >= r.min
+                    AND fb.language_ok AND fb.pages_ok AND fb.reviews_ok
+                    AND fb.categories_ok AND fb.authors_ok
+                    GROUP BY r.min
+                ) r
+            ) AS "ratings!: _",
+            (
+                SELECT jsonb_build_object(
+                    'with', count(*) FILTER (WHERE fb.reviewed),
+                    'without', count(*) FILTER (WHERE NOT fb.reviewed)
+                )
+                FROM fb
+                WHERE fb.language_ok AND fb.pages_ok AND fb.rating_ok
+                AND fb.categories_ok AND fb.authors_ok
+            ) AS "reviews!: _""#,
+        &q as &_,
+        &lang as &_,
+        &languages as &_,
+        &min_pages as &_,
+        &max_pages as &_,
+        &min_rating as &_,
+        &has_reviews as &_,
+        &categories as &_,
+        &all_categories,
+        &authors as &_,
+        &all_authors,
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
     transaction.commit().await?;
-    Ok(response(StatusCode::OK, None, AppJson(books_list)))
+    Ok(response(
+        StatusCode::OK,
+        None,
+        AppJson(ReadResponse { books, facets }),
+    ))
+}
+
+#[derive(Clone, Copy, Debug, sqlx::Type, serde::Deserialize, serde::Serialize)]
+#[sqlx(type_name = "bformat", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum BookFormat {
+    Hardcover,
+    Paperback,
+    Ebook,
+    Audiobook,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct BookEdition {
+    id: i64,
+    /// Always an ISBN-13.
+    isbn: String,
+    publisher: Option<String>,
+    published_on: Option<NaiveDate>,
+    format: BookFormat,
+    pages: Option<i32>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SeriesBook {
+    title: String,
+    name: String,
+    position: i32,
+}
+#[derive(serde::Serialize)]
+pub struct BookSeries {
+    id: i64,
+    name: String,
+    /// Where the book is in the series.
+    position: i32,
+    /// The book that comes after it in the series, if any.
+    next: Option<sqlx::types::Json<SeriesBook>>,
+}
+#[derive(serde::Serialize)]
+pub struct ReadSlugResponse {
+    #[serde(flatten)]
+    book: Book,
+    /// The editions of the book, newest first.
+    editions: Vec<BookEdition>,
+    series: Option<BookSeries>,
 }
 
 pub async fn read_slug(
@@ -269,8 +575,8 @@ pub async fn read_slug(
             b.name AS "name!",
             b.summary AS "summary!",
             b.lang AS "language!",
-            b.cover_image AS "cover_image!: _",
-            b.spine_image AS "spine_image!: _",
+            b.cover_image AS "cover_image?: _",
+            b.spine_image AS "spine_image?: _",
             b.authors AS "authors!: _",
             b.categories AS "categories!: _",
             b.reactions AS "reactions?: _",
@@ -292,11 +598,399 @@ pub async fn read_slug(
     .fetch_one(&mut *transaction)
     .await
     .map_err(|e| match e {
-        sqlx::Error::RowNotFound => BooksError::BookNotFound(slug),
+        sqlx::Error::RowNotFound => BooksError::BookNotFound(slug.clone()),
         _ => BooksError::Unexpected,
      })?;
+    let editions = sqlx::query_as!(
+        BookEdition,
+        r#"SELECT be.id, be.isbn, be.publisher, be.published_on, be.format AS "format: _", be.pages
+        FROM book_editions be
+        JOIN books b ON b.id = be.book_id
+        WHERE b.name = $1
+        ORDER BY be.published_on DESC NULLS LAST, be.id DESC"#,
+        &slug,
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    let series = sqlx::query_as!(
+        BookSeries,
+        r#"SELECT
+            bs.id,
+            bs.name,
+            b.series_position AS "position!",
+            (
+                SELECT jsonb_build_object('title', n.title, 'name', n.name, 'position', n.series_position)
+                FROM books n
+                WHERE n.series_id = bs.id AND n.series_position > b.series_position
+                ORDER BY n.series_position
+                LIMIT 1
+            ) AS "next?: _"
+        FROM books b
+        JOIN book_series bs ON bs.id = b.series_id
+        WHERE b.name = $1"#,
+        &slug,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
     transaction.commit().await?;
-    Ok(response(StatusCode::OK, None, AppJson(book)))
+    Ok(response(
+        StatusCode::OK,
+        None,
+        AppJson(ReadSlugResponse {
+            book,
+            editions,
+            series,
+        }),
+    ))
+}
+
+#[derive(serde::Deserialize, Validate)]
+pub struct CreateEditionPayload {
+    /// An ISBN-10 or an ISBN-13, with or without hyphens.
+    #[validate(custom(function = "isbn_is_valid"))]
+    isbn: String,
+    #[validate(length(min = 1, message = "Publisher must not be empty!"))]
+    publisher: Option<String>,
+    published_on: Option<NaiveDate>,
+    format: BookFormat,
+    #[validate(range(min = 0, message = "Number of pages must be a number!"))]
+    pages: Option<i32>,
+}
+#[derive(serde::Serialize)]
+pub struct CreateEditionResponse {
+    id: i64,
+    isbn: String,
+}
+
+#[instrument(name = "Adding an edition to a book", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn create_edition(
+    State(AppState { pool, .. }): State<AppState>,
+    AdminClaims(claims): AdminClaims,
+    Path(slug): Path<String>,
+    AppJson(CreateEditionPayload {
+        isbn,
+        publisher,
+        published_on,
+        format,
+        pages,
+    }): AppJson<CreateEditionPayload>,
+) -> Result<Response, AppError> {
+    let isbn = isbn::normalize(&isbn).ok_or(BooksError::Unexpected)?;
+    let id = sqlx::query_scalar!(
+        "INSERT INTO book_editions (book_id, isbn, publisher, published_on, format, pages)
+        SELECT b.id, $2, $3, $4, $5, $6 FROM books b WHERE b.name = $1
+        RETURNING id",
+        &slug,
+        &isbn,
+        &publisher as &_,
+        &published_on as &_,
+        &format as &_,
+        &pages as &_,
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::Database(ref db_err)
+            if db_err.constraint() == Some("book_editions_isbn_key") =>
+        {
+            AppError::from(BooksError::IsbnAlreadyExists(isbn.clone()))
+        }
+        err => AppError::from(err),
+    })?
+    .ok_or(BooksError::BookNotFound(slug))?;
+    Ok(response(
+        StatusCode::CREATED,
+        None,
+        AppJson(CreateEditionResponse { id, isbn }),
+    ))
+}
+
+#[derive(serde::Deserialize, Validate)]
+pub struct CreateSeriesPayload {
+    #[validate(length(min = 1, max = 200, message = "Name is either too short or too long!"))]
+    name: String,
+}
+#[derive(serde::Serialize)]
+pub struct CreateSeriesResponse {
+    id: i64,
+}
+
+#[instrument(name = "Creating a series", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn create_series(
+    State(AppState { pool, .. }): State<AppState>,
+    AdminClaims(claims): AdminClaims,
+    AppJson(CreateSeriesPayload { name }): AppJson<CreateSeriesPayload>,
+) -> Result<Response, AppError> {
+    let id = sqlx::query_scalar!(
+        "INSERT INTO book_series (name) VALUES ($1) RETURNING id",
+        &name
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+            AppError::from(BooksError::SeriesAlreadyExists(name.clone()))
+        }
+        err => AppError::from(err),
+    })?;
+    Ok(response(
+        StatusCode::CREATED,
+        None,
+        AppJson(CreateSeriesResponse { id }),
+    ))
+}
+
+#[derive(serde::Deserialize, Validate)]
+#[validate(schema(function = "validate_update_series_payload"))]
+pub struct UpdateSeriesPayload {
+    /// The series the book belongs to, or none when `null`.
+    series_id: Option<i64>,
+    /// Where the book is in the series, starting from 1.
+    #[validate(range(min = 1, message = "Position must be at least 1!"))]
+    position: Option<i32>,
+}
+
+fn validate_update_series_payload(payload: &UpdateSeriesPayload) -> Result<(), ValidationError> {
+    if payload.series_id.is_some() != payload.position.is_some() {
+        return Err(ValidationError::new(
+            "A series and a position must be given together.",
+        ));
+    }
+    Ok(())
+}
+
+/// Places the book `slug` in a series, or takes it out of its series.
+#[instrument(name = "Updating a book's series", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn update_series(
+    State(AppState { pool, .. }): State<AppState>,
+    AdminClaims(claims): AdminClaims,
+    Path(slug): Path<String>,
+    AppJson(UpdateSeriesPayload {
+        series_id,
+        position,
+    }): AppJson<UpdateSeriesPayload>,
+) -> Result<Response, AppError> {
+    let update_result = sqlx::query!(
+        "UPDATE books SET series_id = $2, series_position = $3 WHERE name = $1",
+        &slug,
+        &series_id as &_,
+        &position as &_,
+    )
+    .execute(&pool)
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::Database(ref db_err) => match db_err.constraint() {
+            Some("books_series_id_fkey") => {
+                AppError::from(BooksError::SeriesNotFound(series_id.unwrap_or_default()))
+            }
+            Some("books_series_position_key") => AppError::from(BooksError::SeriesPositionTaken(
+                position.unwrap_or_default(),
+            )),
+            _ => AppError::from(err),
+        },
+        err => AppError::from(err),
+    })?;
+    if update_result.rows_affected() == 0 {
+        return Err(BooksError::BookNotFound(slug).into());
+    }
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[derive(serde::Deserialize, Validate)]
+#[validate(schema(function = "validate_import_payload"))]
+pub struct ImportPayload {
+    /// The files to import in order, from the import directory.
+    #[validate(length(min = 1, message = "At least one file must be imported!"))]
+    files: Vec<String>,
+    format: import::Format,
+    /// The language Open Library works are imported with, as they do not
+    /// have one.
+    #[validate(length(min = 1, message = "Language is not valid!"))]
+    language: Option<String>,
+}
+
+fn validate_import_payload(payload: &ImportPayload) -> Result<(), ValidationError> {
+    if !payload.files.iter().all(|file| path_is_valid(file)) {
+        return Err(ValidationError::new(
+            "Files must be directly in the import directory.",
+        ));
+    }
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+pub struct ImportResponse {
+    /// The id of the import job, see `read_import`.
+    id: i64,
+}
+
+/// Starts importing books from files in the import directory, see
+/// `jobs::import`. Imports run in the background, as they can take longer
+/// than a request.
+#[instrument(name = "Importing books...", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn import(
+    State(AppState { pool, .. }): State<AppState>,
+    AdminClaims(claims): AdminClaims,
+    AppJson(ImportPayload {
+        files,
+        format,
+        language,
+    }): AppJson<ImportPayload>,
+) -> Result<Response, AppError> {
+    let dir = std::path::Path::new(&SETTINGS.import.dir);
+    let paths = files.iter().map(|file| dir.join(file)).collect::<Vec<_>>();
+    let id = import::start(
+        &pool,
+        claims.sub,
+        &paths,
+        format,
+        language.as_deref().unwrap_or(import::DEFAULT_LANGUAGE),
+        SETTINGS.import.batch,
+    )
+    .await?;
+    Ok(response(
+        StatusCode::ACCEPTED,
+        None,
+        AppJson(ImportResponse { id }),
+    ))
+}
+
+/// Returns whether an import is still running, and what it did once it is
+/// done.
+#[instrument(name = "Reading an import...", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn read_import(
+    State(AppState { pool, .. }): State<AppState>,
+    AdminClaims(claims): AdminClaims,
+    Path(id): Path<i64>,
+) -> Result<Response, AppError> {
+    let job = import::read_job(&pool, id).await?;
+    Ok(response(StatusCode::OK, None, AppJson(job)))
+}
+
+#[derive(serde::Deserialize, Validate)]
+pub struct SuggestQuery {
+    #[validate(custom(function = "suggest_query_is_valid"))]
+    q: String,
+    #[validate(range(min = 1, max = 20, message = "Limit must be between 1 and 20!"))]
+    limit: Option<i64>,
+}
+/// The query is trimmed before it is matched, so its length is checked
+/// without the spaces around it. Otherwise, blank queries would match
+/// everything.
+fn suggest_query_is_valid(q: &str) -> Result<(), ValidationError> {
+    if (2..=100).contains(&q.trim().chars().count()) {
+        return Ok(());
+    }
+    Err(ValidationError::new("length")
+        .with_message("Query must be between 2 and 100 characters long!".into()))
+}
+
+#[derive(serde::Serialize)]
+pub struct Suggestion {
+    /// What was matched: `book`, `author` or `category`.
+    kind: String,
+    id: i64,
+    /// The title of the book, or the name of the author or category.
+    label: String,
+    /// Only set for books.
+    slug: Option<String>,
+    score: f32,
+}
+
+/// Escapes the characters `LIKE` treats as wildcards.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Suggests books, authors and categories for a partial query, as typed in
+/// a search box. Names match when they contain the query, or when one of
+/// their words is similar enough to it to tolerate typos. Matches at the
+/// start of a name, then at the start of a word, are ranked first.
+#[instrument(name = "Suggesting books...", skip(pool))]
+pub async fn suggest(
+    State(AppState { pool, .. }): State<AppState>,
+    AppQuery(SuggestQuery { q, limit }): AppQuery<SuggestQuery>,
+) -> Result<Response, AppError> {
+    let q = q.trim().to_lowercase();
+    let pattern = escape_like(&q);
+    let suggestions = sqlx::query_as!(
+        Suggestion,
+        r#"SELECT
+            s.kind AS "kind!",
+            s.id AS "id!",
+            s.label AS "label!",
+            s.slug,
+            s.score AS "score!"
+        FROM (
+            SELECT 'book' AS kind, b.id, b.title AS label, b.name AS slug, lower(b.title) AS value
+            FROM books b
+            WHERE lower(b.title) LIKE '%' || $2 || '%' OR $1 <% lower(b.title)
+            UNION ALL
+            SELECT 'author', ba.id, ba.name, NULL, lower(ba.name)
+            FROM book_authors ba
+            WHERE lower(ba.name) LIKE '%' || $2 || '%' OR $1 <% lower(ba.name)
+            UNION ALL
+            SELECT 'category', bc.id, bc.name, NULL, lower(bc.name)
+            FROM book_categories bc
+            WHERE lower(bc.name) LIKE '%' || $2 || '%' OR $1 <% lower(bc.name)
+        ) m
+        JOIN LATERAL (
+            SELECT m.kind, m.id, m.label, m.slug, word_similarity($1, m.value) + CASE
+                WHEN m.value LIKE $2 || '%' THEN 1
+                WHEN m.value LIKE '% ' || $2 || '%' THEN 0.5
+                ELSE 0
+            END::REAL AS score
+        ) s ON TRUE
+        ORDER BY s.score DESC, length(s.label), s.label
+        LIMIT $3"#,
+        &q,
+        &pattern,
+        limit.unwrap_or(10),
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(response(StatusCode::OK, None, AppJson(suggestions)))
+}
+
+#[derive(serde::Deserialize, Validate)]
+pub struct TrendingQuery {
+    #[validate(range(min = 1, max = 50, message = "Limit must be between 1 and 50!"))]
+    pub limit: Option<i64>,
+}
+
+/// Reads the books with the most reviews, trackers and reactions to their
+/// reviews lately. Recent activity weighs more, see `trending_decay`.
+#[instrument(name = "Reading trending books...", skip(pool))]
+pub async fn trending(
+    State(AppState { pool, .. }): State<AppState>,
+    AppQuery(TrendingQuery { limit }): AppQuery<TrendingQuery>,
+) -> Result<Response, AppError> {
+    let books = sqlx::query_as!(
+        Book,
+        r#"SELECT
+            b.title AS "title!",
+            b.name AS "name!",
+            b.summary AS "summary!",
+            b.lang AS "language!",
+            b.cover_image AS "cover_image?: _",
+            b.spine_image AS "spine_image?: _",
+            b.authors AS "authors!: _",
+            b.categories AS "categories!: _",
+            b.reactions AS "reactions?: _",
+            NULL::JSONB AS "reviews?: _"
+        FROM trending_books tb
+        JOIN books_view b ON b.id = tb.book_id
+        WHERE tb.score > 0
+        ORDER BY tb.score DESC, b.id DESC
+        LIMIT $1"#,
+        limit.unwrap_or(20),
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(response(StatusCode::OK, None, AppJson(books)))
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -383,6 +1077,8 @@ pub struct CreateTrackerPayload {
     book_name: String,
     starts_at: NaiveDate,
     ends_at: NaiveDate,
+    /// The edition being read.
+    edition_id: Option<i64>,
 }
 
 fn validate_create_tracker_payload(payload: &CreateTrackerPayload) -> Result<(), ValidationError> {
@@ -407,6 +1103,7 @@ pub async fn create_tracker(
         book_name,
         starts_at,
         ends_at,
+        edition_id,
     }): AppForm<CreateTrackerPayload>,
 ) -> Result<Response, AppError> {
     let mut tx = pool.begin().await?;
@@ -418,11 +1115,12 @@ pub async fn create_tracker(
             err => AppError::from(err),
         })?;
     sqlx::query!(
-        "INSERT INTO users_books (user_id, book_id, starts_at, ends_at) VALUES ($1, $2, $3, $4)",
+        "INSERT INTO users_books (user_id, book_id, starts_at, ends_at, edition_id) VALUES ($1, $2, $3, $4, $5)",
         &claims.sub,
         &book.id,
         &starts_at,
-        &ends_at
+        &ends_at,
+        &edition_id as &_,
     )
     .execute(&mut *tx)
     .await
@@ -430,6 +1128,8 @@ pub async fn create_tracker(
         sqlx::Error::Database(ref err) => {
             if err.is_unique_violation() {
                 AppError::from(BooksError::AlreadyTracking(book.title))
+            } else if err.constraint() == Some("users_books_edition_fkey") {
+                AppError::from(BooksError::EditionNotFound(edition_id.unwrap_or_default()))
             } else {
                 AppError::from(e)
             }
@@ -452,6 +1152,7 @@ pub struct ReadingTracker {
     ends_at: NaiveDate,
     pages_read: i64,
     completed: bool,
+    edition: Option<sqlx::types::Json<BookEdition>>,
 }
 
 #[instrument(name = "Fetching tracker...", skip(pool, claims), fields(uid = %claims.sub))]
@@ -471,9 +1172,11 @@ pub async fn fetch_tracker(
             ub.starts_at,
             ub.ends_at,
             ub.pages_read,
-            ub.completed
+            ub.completed,
+            to_jsonb(be) AS "edition?: _"
         FROM users_books ub
         JOIN books_view b ON ub.book_id = b.id
+        LEFT JOIN book_editions be ON be.id = ub.edition_id
         WHERE
             user_id = $1 AND
             b.name = $2 AND
@@ -1,13 +1,16 @@
 use super::{
     auth::{OptionalUserClaims, UserClaims},
     reactions::{PostReaction, PostReactionMetadata},
+    users::UserError,
 };
 use crate::{
     app::AppState,
     utils::{
+        constants::MAX_COMMENT_CONTENT_LENGTH,
         errors::AppError,
+        markdown, mentions,
         response::response,
-        structs::{AppImage, AppJson, AppQuery},
+        structs::{AppImage, AppJson, AppQuery, ContentSpan},
     },
 };
 use axum::{
@@ -33,6 +36,12 @@ pub struct Comment {
     pub id: i64,
     pub post_id: i64,
     pub content: String,
+    /// `content` rendered as sanitized HTML.
+    pub content_html: Option<String>,
+    pub content_spans: sqlx::types::Json<Vec<ContentSpan>>,
+    /// Whether the viewer has opted into seeing spoilers of books they
+    /// have finished, and has finished the book this comment is about.
+    pub reveal_spoilers: bool,
     pub author_name: String,
     pub author_picture: Option<sqlx::types::Json<AppImage>>,
     pub reactions: Option<sqlx::types::Json<PostReactionMetadata>>,
@@ -48,7 +57,7 @@ pub struct CreatePayload {
     post_id: i64,
     #[validate(range(min = 0))]
     parent_id: Option<i64>,
-    #[validate(length(min = 1))]
+    #[validate(length(min = 1, max = MAX_COMMENT_CONTENT_LENGTH))]
     content: String,
 }
 #[derive(serde::Serialize)]
@@ -67,32 +76,53 @@ pub async fn create(
     }): AppJson<CreatePayload>,
 ) -> Result<Response, AppError> {
     let mut transaction = pool.begin().await?;
+    let is_blocked = sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+            SELECT FROM posts p WHERE p.id = $2 AND is_blocked_by($1, p.author_id)
+            UNION ALL
+            SELECT FROM comments c WHERE c.id = $3 AND is_blocked_by($1, c.author_id)
+        ) AS "is_blocked!""#,
+        &claims.sub,
+        &post_id,
+        &parent_id as &_,
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    if is_blocked {
+        return Err(UserError::Blocked.into());
+    }
+    let content_html = markdown::render(&content);
     let query = {
         if let Some(parent) = parent_id {
             sqlx::query_scalar!(
-                "INSERT INTO comments (post_id, author_id, content, path)
-                VALUES ($1, $2, $3, (SELECT path || TEXT2LTREE(id::VARCHAR(255)) FROM comments WHERE id = $4))
+                "INSERT INTO comments (post_id, author_id, content, content_html, path)
+                VALUES ($1, $2, $3, $4, (SELECT path || TEXT2LTREE(id::VARCHAR(255)) FROM comments WHERE id = $5))
                 RETURNING id",
                 &post_id,
                 &claims.sub,
                 &content,
+                &content_html,
                 &parent,
             )
         } else {
             sqlx::query_scalar!(
-                "INSERT INTO comments (post_id, author_id, content, path) VALUES ($1, $2, $3, 'Top') RETURNING id",
+                "INSERT INTO comments (post_id, author_id, content, content_html, path)
+                VALUES ($1, $2, $3, $4, 'Top')
+                RETURNING id",
                 &post_id,
                 &claims.sub,
                 &content,
+                &content_html,
             )
         }
     };
-    let post_id = query.fetch_one(&mut *transaction).await?;
+    let comment_id = query.fetch_one(&mut *transaction).await?;
+    mentions::link_comment(&mut transaction, comment_id, &content).await?;
     transaction.commit().await?;
     Ok(response(
         StatusCode::CREATED,
         None,
-        AppJson(CreateResponse { id: post_id }),
+        AppJson(CreateResponse { id: comment_id }),
     ))
 }
 
@@ -115,6 +145,11 @@ pub struct ReadQuery {
     /// specified, this query is ignored.
     #[validate(range(min = 0, message = "`previous_last` must point to a valid comment!"))]
     previous_last: Option<i64>,
+    /// If specified, we will fetch comments mentioning this
+    /// user at any depth, not just top-level ones. When
+    /// `comment_id` is specified, this query is ignored.
+    #[validate(length(min = 1, message = "`mentioned` must point to a valid user!"))]
+    mentioned: Option<String>,
 }
 
 #[instrument(name = "Reading a comment", skip(pool, claims))]
@@ -126,6 +161,7 @@ pub async fn read(
         post_id,
         comment_id,
         previous_last,
+        mentioned,
     }): AppQuery<ReadQuery>,
 ) -> Result<Response, AppError> {
     let mut transaction = pool.begin().await?;
@@ -136,6 +172,9 @@ pub async fn read(
             c.id AS "id!",
             c.post_id AS "post_id!",
             c.content AS "content!",
+            c.content_html AS "content_html?",
+            c.content_spans AS "content_spans!: _",
+            c.reveal_spoilers AS "reveal_spoilers!",
             c.author_name AS "author_name!",
             c.author_picture AS "author_picture?: _",
             c.reactions AS "reactions?: _",
@@ -151,6 +190,10 @@ pub async fn read(
             WHEN $3::TEXT IS NOT NULL AND c.author_name = $3::TEXT THEN TRUE
             ELSE FALSE
         END AND CASE
+            WHEN $4::BIGINT IS NULL AND $6::TEXT IS NOT NULL THEN EXISTS (
+                SELECT FROM comment_mentions cm JOIN users u ON u.id = cm.user_id
+                WHERE cm.comment_id = c.id AND u.name = $6::TEXT
+            ) AND ($5::BIGINT IS NULL OR c.id < $5::BIGINT)
             WHEN $4::BIGINT IS NULL AND $5::BIGINT IS NULL AND c.path = 'Top' THEN TRUE
             WHEN $4::BIGINT IS NULL AND $5::BIGINT IS NOT NULL AND c.path = 'Top' AND c.id < $5::BIGINT THEN TRUE
             WHEN $4::BIGINT IS NOT NULL AND c.id = $4::BIGINT THEN TRUE
@@ -163,6 +206,7 @@ pub async fn read(
         &user as &_,
         &comment_id as &_,
         &previous_last as &_,
+        &mentioned as &_,
     )
     .fetch_all(&mut *transaction)
     .await?;
@@ -199,6 +243,9 @@ pub async fn read_replies(
             c.id AS "id!",
             c.post_id AS "post_id!",
             c.content AS "content!",
+            c.content_html AS "content_html?",
+            c.content_spans AS "content_spans!: _",
+            c.reveal_spoilers AS "reveal_spoilers!",
             c.author_name AS "author_name!",
             c.author_picture AS "author_picture?: _",
             c.reactions AS "reactions?: _",
@@ -229,7 +276,7 @@ pub async fn read_replies(
 pub struct UpdatePayload {
     #[validate(range(min = 0))]
     id: i64,
-    #[validate(length(min = 1))]
+    #[validate(length(min = 1, max = MAX_COMMENT_CONTENT_LENGTH))]
     content: String,
 }
 
@@ -241,9 +288,10 @@ pub async fn update(
 ) -> Result<impl IntoResponse, AppError> {
     let mut transaction = pool.begin().await?;
     let update_result = sqlx::query!(
-        "UPDATE comments SET content = $2 WHERE id = $1 AND author_id = $3",
+        "UPDATE comments SET content = $2, content_html = $3 WHERE id = $1 AND author_id = $4",
         &id,
         &content,
+        &markdown::render(&content),
         &claims.sub
     )
     .execute(&mut *transaction)
@@ -251,6 +299,7 @@ pub async fn update(
     if update_result.rows_affected() == 0 {
         return Err(CommentsError::UpdateUnauthorized(id))?;
     }
+    mentions::link_comment(&mut transaction, id, &content).await?;
     transaction.commit().await?;
     Ok(StatusCode::NO_CONTENT)
 }
@@ -1,27 +1,38 @@
-use super::auth::UserClaims;
+use super::auth::{AdminClaims, OptionalUserClaims, UserClaims};
 use crate::{
     app::AppState,
+    jobs::gc,
+    settings::SETTINGS,
+    storage::{PresignedRequest, StorageBackend, StorageError},
     utils::{
-        // constants::UPLOADS_DIRECTORY,
         errors::AppError,
         futures::flatten,
         image::Image,
-        response::response,
-        structs::{AppJson, AppMultipart},
-        uploads::{upload_file, UploadsError},
+        processing,
+        response::{empty, response},
+        structs::{AppJson, AppMultipart, AppQuery},
+        uploads::{
+            check_quota, check_size, check_type, complete_upload, delete_file, delete_objects,
+            new_key, sign_key, upload_file, usage, verify_key, FileStatus, FileVisibility,
+            UploadsError,
+        },
+        validators::{quality_is_allowed, width_is_allowed},
     },
 };
 use axum::{
     body::Bytes,
     extract::{Path, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::Response,
 };
+use axum_extra::headers::{
+    ETag, HeaderMapExt, IfModifiedSince, IfNoneMatch, IfRange, LastModified, Range,
+};
 use axum_typed_multipart::{FieldData, TryFromMultipart};
-use futures::future::TryJoinAll;
-// use libc::{O_CLOEXEC, O_RDONLY};
-// use std::io::Read;
-// use std::path::Path as OsPath;
+use chrono::{DateTime, Utc};
+use futures::{future::TryJoinAll, TryStreamExt};
+use image::ImageFormat;
+use std::{ops::Bound, time::Duration};
 use tokio::try_join;
 use tracing::instrument;
 use validator::Validate;
@@ -29,71 +40,645 @@ use validator::Validate;
 #[derive(TryFromMultipart, Validate)]
 pub struct UploadPayload {
     files: Vec<FieldData<Bytes>>,
+    /// Defaults to public.
+    visibility: Option<FileVisibility>,
+}
+#[derive(serde::Serialize)]
+pub struct UploadedFile {
+    file_id: i64,
+    /// `quarantined` if the scanner flagged the file.
+    status: FileStatus,
 }
 #[derive(serde::Serialize)]
 pub struct UploadResponse {
-    file_ids: Vec<i64>,
+    /// The uploaded files, in the order they were sent.
+    files: Vec<UploadedFile>,
 }
 
-#[instrument(name = "Uploading files...", skip(pool, s3, claims, files), fields(
+#[instrument(name = "Uploading files...", skip(pool, storage, scanner, claims, files), fields(
     uid = %claims.sub,
     file_name = ?files.iter().map(|file| file.metadata.file_name.clone().unwrap_or("None".to_owned())).collect::<Vec<_>>()
 ))]
 pub async fn upload(
-    State(AppState { pool, s3, .. }): State<AppState>,
+    State(AppState {
+        pool,
+        storage,
+        scanner,
+        ..
+    }): State<AppState>,
     claims: UserClaims,
-    AppMultipart(UploadPayload { files }): AppMultipart<UploadPayload>,
+    AppMultipart(UploadPayload { files, visibility }): AppMultipart<UploadPayload>,
 ) -> Result<Response, AppError> {
+    let visibility = visibility.unwrap_or(FileVisibility::Public);
     let mut tasks = Vec::with_capacity(files.len());
     for file in files {
         let pool = pool.clone();
-        let s3 = s3.clone();
+        let storage = storage.clone();
+        let scanner = scanner.clone();
         tasks.push(flatten(tokio::spawn(async move {
             let mut transaction = pool.begin().await?;
-            let file_id = upload_file(&mut transaction, &s3, claims.sub, None, file).await?;
+            let (file_id, status) = upload_file(
+                &mut transaction,
+                &*storage,
+                &*scanner,
+                claims.sub,
+                None,
+                visibility,
+                file,
+            )
+            .await?;
             transaction.commit().await?;
-            Ok::<i64, AppError>(file_id)
+            Ok::<_, AppError>(UploadedFile { file_id, status })
         })));
     }
-    let (file_ids,) = try_join!(tasks.into_iter().collect::<TryJoinAll<_>>())?;
+    let (files,) = try_join!(tasks.into_iter().collect::<TryJoinAll<_>>())?;
     Ok(response(
         StatusCode::CREATED,
         None,
-        AppJson(UploadResponse { file_ids }),
+        AppJson(UploadResponse { files }),
     ))
 }
 
-pub async fn load<'c>(
-    State(AppState { s3, .. }): State<AppState>,
-    Path(path): Path<String>,
-) -> Result<Image, AppError> {
-    // let mime = mime_guess::from_path(path.clone())
-    //     .first_raw()
-    //     .map(HeaderValue::from_static)
-    //     .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+#[derive(serde::Deserialize, Validate)]
+pub struct PresignPayload {
+    /// The MIME type of the file that will be uploaded.
+    #[validate(length(min = 1, message = "Content type must not be empty!"))]
+    content_type: String,
+    /// The exact size of the file that will be uploaded, in bytes.
+    #[validate(range(min = 1, message = "Size must be a positive number!"))]
+    size: u64,
+    /// Defaults to public.
+    visibility: Option<FileVisibility>,
+}
+#[derive(serde::Serialize)]
+pub struct PresignResponse {
+    file_id: i64,
+    #[serde(flatten)]
+    request: PresignedRequest,
+}
+
+/// Reserves a file and returns a request that lets the client upload it
+/// directly to storage. The file cannot be used until the upload is completed.
+#[instrument(name = "Presigning an upload...", skip(pool, storage, claims), fields(uid = %claims.sub))]
+pub async fn presign(
+    State(AppState { pool, storage, .. }): State<AppState>,
+    claims: UserClaims,
+    AppJson(PresignPayload {
+        content_type,
+        size,
+        visibility,
+    }): AppJson<PresignPayload>,
+) -> Result<Response, AppError> {
+    check_size(size)?;
+    let format = processing::from_mime_type(&content_type)?;
+    check_type(format)?;
+    let ext = format.extensions_str()[0];
+    let key = new_key(ext);
+    let mut transaction = pool.begin().await?;
+    check_quota(&mut transaction, claims.sub, None, size).await?;
+    // The announced size counts towards the quota until the upload is
+    // completed, which records the actual one.
+    let file_id = sqlx::query_scalar!(
+        "INSERT INTO files (owner_id, ext, path, status, key, visibility, size)
+        VALUES ($1, $2, 'Top', 'pending', $3, $4, $5)
+        RETURNING id",
+        &claims.sub,
+        ext,
+        &key,
+        visibility.unwrap_or(FileVisibility::Public) as FileVisibility,
+        size as i64,
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    let request = storage
+        .presign_put(
+            &key,
+            &content_type,
+            size,
+            Duration::from_secs(SETTINGS.storage.presign.exp),
+        )
+        .await
+        .map_err(UploadsError::from)?;
+    transaction.commit().await?;
+    Ok(response(
+        StatusCode::CREATED,
+        None,
+        AppJson(PresignResponse { file_id, request }),
+    ))
+}
+
+#[derive(serde::Deserialize, Validate)]
+pub struct CompletePayload {
+    file_id: i64,
+}
+#[derive(serde::Serialize)]
+pub struct CompleteResponse {
+    file_id: i64,
+    /// `quarantined` if the scanner flagged the file.
+    status: FileStatus,
+}
+
+/// Verifies that a presigned upload went through and processes the file.
+#[instrument(name = "Completing an upload...", skip(pool, storage, scanner, claims), fields(uid = %claims.sub))]
+pub async fn complete(
+    State(AppState {
+        pool,
+        storage,
+        scanner,
+        ..
+    }): State<AppState>,
+    claims: UserClaims,
+    AppJson(CompletePayload { file_id }): AppJson<CompletePayload>,
+) -> Result<Response, AppError> {
+    let mut transaction = pool.begin().await?;
+    let status =
+        complete_upload(&mut transaction, &*storage, &*scanner, claims.sub, file_id).await?;
+    transaction.commit().await?;
+    Ok(response(
+        StatusCode::OK,
+        None,
+        AppJson(CompleteResponse { file_id, status }),
+    ))
+}
+
+#[derive(serde::Serialize)]
+pub struct UsageResponse {
+    /// How many bytes the user stores, variants included.
+    used: i64,
+    /// How many bytes the user can store.
+    quota: u64,
+    /// The largest file the user can upload, in bytes.
+    limit: u64,
+    /// The MIME types of the files the user can upload.
+    types: Vec<String>,
+}
+
+#[instrument(name = "Reading storage usage...", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn read_usage(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: UserClaims,
+) -> Result<Response, AppError> {
+    let limits = &SETTINGS.storage.limits;
+    Ok(response(
+        StatusCode::OK,
+        None,
+        AppJson(UsageResponse {
+            used: usage(&pool, claims.sub, None).await?,
+            quota: limits.quota,
+            limit: limits.size,
+            types: limits.types.clone(),
+        }),
+    ))
+}
+
+#[derive(serde::Serialize)]
+pub struct FileReference {
+    /// How the file is used: `cover`, `spine` or `picture`.
+    kind: String,
+    /// The slug of the book or the name of the user.
+    name: String,
+}
+#[derive(serde::Serialize)]
+pub struct FileMetadata {
+    id: i64,
+    ext: String,
+    key: String,
+    mime: String,
+    /// The size in bytes, unknown for files uploaded before it was recorded.
+    size: Option<i64>,
+    width: Option<i32>,
+    height: Option<i32>,
+    status: FileStatus,
+    visibility: FileVisibility,
+    created_at: DateTime<Utc>,
+    /// Only included when reading a single file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    references: Option<Vec<FileReference>>,
+}
+
+fn mime(ext: &str) -> String {
+    mime_guess::from_ext(ext)
+        .first_raw()
+        .unwrap_or("application/octet-stream")
+        .to_owned()
+}
+
+#[derive(serde::Deserialize, Validate)]
+pub struct ListQuery {
+    #[validate(range(min = 0, message = "`previous_last` must point to a valid file!"))]
+    previous_last: Option<i64>,
+}
+
+#[instrument(name = "Listing files...", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn list(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: UserClaims,
+    AppQuery(ListQuery { previous_last }): AppQuery<ListQuery>,
+) -> Result<Response, AppError> {
+    let files = sqlx::query!(
+        r#"SELECT
+            id,
+            ext,
+            key,
+            size,
+            width,
+            height,
+            status AS "status: FileStatus",
+            visibility AS "visibility: FileVisibility",
+            created_at
+        FROM files
+        WHERE owner_id = $1 AND variant IS NULL AND CASE
+            WHEN $2::BIGINT IS NULL THEN TRUE
+            WHEN $2::BIGINT IS NOT NULL AND id < $2::BIGINT THEN TRUE
+            ELSE FALSE
+        END
+        ORDER BY id DESC
+        LIMIT 20"#,
+        &claims.sub,
+        &previous_last as &_,
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|file| FileMetadata {
+        id: file.id,
+        mime: mime(&file.ext),
+        ext: file.ext,
+        key: file.key,
+        size: file.size,
+        width: file.width,
+        height: file.height,
+        status: file.status,
+        visibility: file.visibility,
+        created_at: file.created_at,
+        references: None,
+    })
+    .collect::<Vec<_>>();
+    Ok(response(StatusCode::OK, None, AppJson(files)))
+}
+
+#[instrument(name = "Reading a file's metadata...", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn read(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: UserClaims,
+    Path(file_id): Path<i64>,
+) -> Result<Response, AppError> {
+    let mut transaction = pool.begin().await?;
+    let file = sqlx::query!(
+        r#"SELECT
+            id,
+            ext,
+            key,
+            size,
+            width,
+            height,
+            status AS "status: FileStatus",
+            visibility AS "visibility: FileVisibility",
+            created_at
+        FROM files
+        WHERE id = $1 AND owner_id = $2 AND variant IS NULL"#,
+        &file_id,
+        &claims.sub,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    .ok_or(UploadsError::FileNotFound(file_id))?;
+    let references = sqlx::query_as!(
+        FileReference,
+        r#"SELECT 'cover' AS "kind!", name AS "name!" FROM books WHERE cover_id = $1
+        UNION ALL
+        SELECT 'spine', name FROM books WHERE spine_id = $1
+        UNION ALL
+        SELECT 'picture', name FROM users WHERE picture_id = $1"#,
+        &file_id,
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(response(
+        StatusCode::OK,
+        None,
+        AppJson(FileMetadata {
+            id: file.id,
+            mime: mime(&file.ext),
+            ext: file.ext,
+            key: file.key,
+            size: file.size,
+            width: file.width,
+            height: file.height,
+            status: file.status,
+            visibility: file.visibility,
+            created_at: file.created_at,
+            references: Some(references),
+        }),
+    ))
+}
+
+#[instrument(name = "Deleting a file...", skip(pool, storage, claims), fields(uid = %claims.sub))]
+pub async fn delete(
+    State(AppState { pool, storage, .. }): State<AppState>,
+    claims: UserClaims,
+    Path(file_id): Path<i64>,
+) -> Result<Response, AppError> {
+    let mut transaction = pool.begin().await?;
+    let keys = delete_file(&mut transaction, claims.sub, file_id).await?;
+    transaction.commit().await?;
+    delete_objects(&*storage, &keys).await?;
+    Ok(empty(None))
+}
+
+#[derive(serde::Serialize)]
+pub struct SignResponse {
+    /// The path to the file, relative to the backend.
+    path: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Creates an expiring URL that lets anyone load one of the user's files,
+/// including private ones.
+#[instrument(name = "Signing a file's URL...", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn sign(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: UserClaims,
+    Path(file_id): Path<i64>,
+) -> Result<Response, AppError> {
+    let key = sqlx::query_scalar!(
+        "SELECT key FROM files WHERE id = $1 AND owner_id = $2",
+        &file_id,
+        &claims.sub,
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(UploadsError::FileNotFound(file_id))?;
+    let expires_at = Utc::now() + chrono::Duration::seconds(SETTINGS.storage.sign.exp);
+    let token = sign_key(&key, expires_at.timestamp())?;
+    Ok(response(
+        StatusCode::OK,
+        None,
+        AppJson(SignResponse {
+            path: format!("/assets/{key}?token={token}"),
+            expires_at,
+        }),
+    ))
+}
+
+#[derive(serde::Deserialize, Validate, Debug)]
+pub struct CollectQuery {
+    /// Whether orphaned files should only be reported, which is the default.
+    dry_run: Option<bool>,
+}
+
+#[instrument(name = "Collecting orphaned files on demand...", skip(pool, storage, claims), fields(uid = %claims.sub))]
+pub async fn collect(
+    State(AppState { pool, storage, .. }): State<AppState>,
+    AdminClaims(claims): AdminClaims,
+    AppQuery(CollectQuery { dry_run }): AppQuery<CollectQuery>,
+) -> Result<Response, AppError> {
+    let grace = Duration::from_secs(SETTINGS.storage.gc.grace);
+    let report = gc::collect(&pool, &*storage, grace, dry_run.unwrap_or(true)).await?;
+    Ok(response(StatusCode::OK, None, AppJson(report)))
+}
 
-    // let mut buf = Vec::<u8>::new();
+/// The part of a file requested with a `Range` header.
+enum ByteRange {
+    Full,
+    Partial(std::ops::Range<u64>),
+    Unsatisfiable,
+}
 
-    // let path = OsPath::new(UPLOADS_DIRECTORY).join(path);
+/// Resolves `range` against a file of `size` bytes. Requests for several
+/// ranges at once are served the whole file, which RFC 9110 allows, and
+/// requests for none that lie within the file cannot be satisfied.
+fn resolve_range(range: &Range, size: u64) -> ByteRange {
+    let mut ranges = range.satisfiable_ranges(size).filter_map(|(start, end)| {
+        let start = match start {
+            Bound::Included(start) => start,
+            Bound::Excluded(start) => start.checked_add(1)?,
+            Bound::Unbounded => return None,
+        };
+        let end = match end {
+            Bound::Included(end) => end.saturating_add(1).min(size),
+            Bound::Excluded(end) => end.min(size),
+            Bound::Unbounded => size,
+        };
+        (start < end).then_some(start..end)
+    });
+    match (ranges.next(), ranges.next()) {
+        (Some(range), None) => ByteRange::Partial(range),
+        (Some(_), Some(_)) => ByteRange::Full,
+        // Suffixes longer than the file are left out, though they stand
+        // for the whole file. They are the only ranges that are kept for a
+        // file as large as can be but not for this one.
+        (None, _)
+            if range.satisfiable_ranges(u64::MAX).count()
+                > range.satisfiable_ranges(size).count() =>
+        {
+            ByteRange::Full
+        }
+        (None, _) => ByteRange::Unsatisfiable,
+    }
+}
 
-    // hdfs.open(path.to_str().unwrap(), O_CLOEXEC | O_RDONLY, None, None)
-    //     .unwrap()
-    //     .read_to_end(&mut buf)
-    //     .unwrap();
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TransformFormat {
+    #[serde(alias = "jpg")]
+    Jpeg,
+    Png,
+    Webp,
+}
 
-    // let body = Body::from(buf);
+impl From<TransformFormat> for ImageFormat {
+    fn from(format: TransformFormat) -> Self {
+        match format {
+            TransformFormat::Jpeg => ImageFormat::Jpeg,
+            TransformFormat::Png => ImageFormat::Png,
+            TransformFormat::Webp => ImageFormat::WebP,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Validate)]
+pub struct LoadQuery {
+    /// The width to resize the image to. Images are never enlarged.
+    #[validate(custom(function = "width_is_allowed"))]
+    w: Option<u32>,
+    /// The format to convert the image to.
+    format: Option<TransformFormat>,
+    /// The quality to encode the image with, only used by JPEG and WebP.
+    #[validate(custom(function = "quality_is_allowed"))]
+    q: Option<u8>,
+    /// A token from a signed URL, which grants access to a private file.
+    token: Option<String>,
+}
 
-    // Ok(([(CONTENT_TYPE, mime)], body).into_response())
+/// Returns the key of the image at `path` transformed as requested,
+/// generating it the first time it is asked for.
+async fn transformed(
+    storage: &dyn StorageBackend,
+    path: &str,
+    LoadQuery { w, format, q, .. }: LoadQuery,
+) -> Result<String, AppError> {
+    // Transformed and quarantined copies are never transformed themselves.
+    if path.starts_with("cache/") || path.starts_with("quarantine/") {
+        return Err(UploadsError::from(StorageError::NotFound(path.to_owned())).into());
+    }
+    // GIFs are served as PNGs since only their first frame is kept.
+    let format = match format {
+        Some(format) => format.into(),
+        None => match std::path::Path::new(path)
+            .extension()
+            .and_then(ImageFormat::from_extension)
+        {
+            Some(ImageFormat::Gif) => ImageFormat::Png,
+            Some(format) => format,
+            None => return Err(UploadsError::UnsupportedFormat.into()),
+        },
+    };
+    // Lossless formats ignore the quality, so it is left out of their
+    // key to not store the same image once per quality.
+    let quality = q.unwrap_or(SETTINGS.images.quality);
+    let key = format!(
+        "cache/{path}/{}{}.{}",
+        w.map_or("full".to_owned(), |w| w.to_string()),
+        if processing::is_lossy(format) {
+            format!("-{quality}")
+        } else {
+            String::new()
+        },
+        format.extensions_str()[0],
+    );
+
+    match storage.head(&key).await {
+        Ok(_) => return Ok(key),
+        Err(StorageError::NotFound(_)) => {}
+        Err(err) => return Err(UploadsError::from(err).into()),
+    }
 
-    let res = s3
-        .get_object()
-        .bucket("blisk-s3")
-        .key(&path)
-        .send()
+    let source: Vec<Bytes> = storage
+        .get(path)
         .await
-        .map_err(|e| UploadsError::from(e))?;
+        .map_err(UploadsError::from)?
+        .body
+        .try_collect()
+        .await
+        .map_err(UploadsError::from)?;
+    let image = tokio::task::spawn_blocking(move || {
+        processing::transform(&source.concat(), w, format, quality)
+    })
+    .await
+    .map_err(|_| UploadsError::Unexpected)??;
+    storage
+        .put(&key, image.bytes.into())
+        .await
+        .map_err(UploadsError::from)?;
+    Ok(key)
+}
+
+#[instrument(
+    name = "Loading a file...",
+    skip(pool, storage, claims, query, headers)
+)]
+pub async fn load(
+    State(AppState { pool, storage, .. }): State<AppState>,
+    claims: Result<OptionalUserClaims, AppError>,
+    Path(path): Path<String>,
+    AppQuery(query): AppQuery<LoadQuery>,
+    headers: HeaderMap,
+) -> Result<Image, AppError> {
+    let file = sqlx::query!(
+        r#"SELECT
+            id,
+            owner_id,
+            status AS "status: FileStatus",
+            visibility AS "visibility: FileVisibility"
+        FROM files
+        WHERE key = $1 AND status <> 'pending'"#,
+        &path,
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or_else(|| UploadsError::from(StorageError::NotFound(path.clone())))?;
+    // Private files are reported as missing so that their keys cannot be
+    // probed. An invalid session only matters when the file is private.
+    let is_private = file.visibility == FileVisibility::Private;
+    if is_private {
+        let is_owner = matches!(claims, Ok(OptionalUserClaims(Some(ref claims))) if claims.sub == file.owner_id);
+        let is_signed = query
+            .token
+            .as_deref()
+            .is_some_and(|token| verify_key(token, &path));
+        if !is_owner && !is_signed {
+            return Err(UploadsError::from(StorageError::NotFound(path)).into());
+        }
+    }
+    if file.status == FileStatus::Quarantined {
+        return Err(UploadsError::FileQuarantined(file.id).into());
+    }
+
+    let (path, name) = match query {
+        LoadQuery {
+            w: None,
+            format: None,
+            q: None,
+            ..
+        } => (path.clone(), path),
+        query => {
+            let key = transformed(&*storage, &path, query).await?;
+            let stem = std::path::Path::new(&path)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("image");
+            let ext = key.rsplit_once('.').map_or("", |(_, ext)| ext);
+            (key.clone(), format!("{stem}.{ext}"))
+        }
+    };
+    let metadata = storage.head(&path).await.map_err(UploadsError::from)?;
+    let etag = metadata.etag().and_then(|etag| etag.parse::<ETag>().ok());
+    let last_modified = metadata.last_modified.map(LastModified::from);
+
+    // `If-Modified-Since` is ignored when `If-None-Match` is present.
+    let is_fresh = match (
+        headers.typed_get::<IfNoneMatch>(),
+        headers.typed_get::<IfModifiedSince>(),
+    ) {
+        (Some(if_none_match), _) => etag
+            .as_ref()
+            .is_some_and(|etag| !if_none_match.precondition_passes(etag)),
+        (None, Some(if_modified_since)) => metadata
+            .last_modified
+            .is_some_and(|last_modified| !if_modified_since.is_modified(last_modified)),
+        (None, None) => false,
+    };
+    if is_fresh {
+        return Ok(Image::NotModified {
+            metadata,
+            is_private,
+        });
+    }
+
+    // A `Range` request whose `If-Range` no longer matches gets the whole file.
+    let range = headers
+        .typed_get::<Range>()
+        .filter(|_| {
+            headers.typed_get::<IfRange>().is_none_or(|if_range| {
+                !if_range.is_modified(etag.as_ref(), last_modified.as_ref())
+            })
+        })
+        .map_or(ByteRange::Full, |range| resolve_range(&range, metadata.size));
 
-    let body: Vec<u8> = res.body.collect().await.unwrap().to_vec();
+    let (object, range) = match range {
+        ByteRange::Full => (storage.get(&path).await, None),
+        ByteRange::Partial(range) => (storage.get_range(&path, range.clone()).await, Some(range)),
+        ByteRange::Unsatisfiable => return Ok(Image::RangeNotSatisfiable(metadata.size)),
+    };
+    let object = object.map_err(UploadsError::from)?;
 
-    Ok((path, body).into())
+    Ok(Image::File {
+        name,
+        metadata,
+        is_private,
+        body: object.body,
+        range,
+    })
 }
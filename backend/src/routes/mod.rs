@@ -5,4 +5,7 @@ pub mod files;
 pub mod health;
 pub mod posts;
 pub mod reactions;
+pub mod recommendations;
+pub mod search;
+pub mod tags;
 pub mod users;
@@ -1,15 +1,18 @@
 use super::{
     auth::{OptionalUserClaims, UserClaims},
-    books::BooksError,
+    books::{BooksError, TrendingQuery},
     comments::Comment,
     reactions::{PostReaction, PostReactionMetadata},
 };
 use crate::{
     app::AppState,
     utils::{
+        constants::MAX_POST_CONTENT_LENGTH,
         errors::AppError,
+        markdown, mentions,
         response::response,
-        structs::{AppImage, AppJson, AppQuery},
+        structs::{nullable, AppImage, AppJson, AppQuery, ContentSpan},
+        validators::rating_is_valid,
     },
 };
 use axum::{
@@ -45,6 +48,14 @@ pub struct Post {
     pub id: i64,
     pub title: String,
     pub content: String,
+    /// `content` rendered as sanitized HTML.
+    pub content_html: Option<String>,
+    pub content_spans: sqlx::types::Json<Vec<ContentSpan>>,
+    /// Whether the author has marked the whole review as containing spoilers.
+    pub spoiler: bool,
+    /// Whether the viewer has opted into seeing spoilers of books
+    /// they have finished, and has finished this post's book.
+    pub reveal_spoilers: bool,
     pub author_name: String,
     pub author_picture: Option<sqlx::types::Json<AppImage>>,
     pub book_title: Option<String>,
@@ -53,6 +64,8 @@ pub struct Post {
     pub book_cover: Option<sqlx::types::Json<AppImage>>,
     pub book_spine: Option<sqlx::types::Json<AppImage>>,
     pub book_reaction: Reaction,
+    /// Out of 5 stars, in half stars.
+    pub rating: Option<f32>,
     pub reactions: Option<sqlx::types::Json<PostReactionMetadata>>,
     pub user_reaction: Option<PostReaction>,
     pub comments: Option<sqlx::types::Json<Vec<Comment>>>,
@@ -64,9 +77,14 @@ pub struct CreatePayload {
     book: String,
     #[validate(length(min = 1, max = 500, message = "Title is either too short or too long!"))]
     title: String,
-    #[validate(length(min = 1, message = "Content is not valid!"))]
+    #[validate(length(min = 1, max = MAX_POST_CONTENT_LENGTH, message = "Content is either too short or too long!"))]
     content: String,
     reaction: Reaction,
+    /// Out of 5 stars, in half stars.
+    #[validate(custom(function = "rating_is_valid"))]
+    rating: Option<f32>,
+    /// Whether the review contains spoilers.
+    spoiler: Option<bool>,
 }
 #[derive(serde::Serialize)]
 pub struct CreateResponse {
@@ -82,6 +100,8 @@ pub async fn create(
         title,
         content,
         reaction,
+        rating,
+        spoiler,
     }): AppJson<CreatePayload>,
 ) -> Result<Response, AppError> {
     let mut transaction = pool.begin().await?;
@@ -99,14 +119,17 @@ pub async fn create(
         return Err(PostsError::BookNotCompleted(user_record.book_title))?;
     }
     let pid: i64 = sqlx::query_scalar!(
-        "INSERT INTO posts (author_id, book_id, title, content, reaction)
-        VALUES ($1, (SELECT id FROM books WHERE name = $2), $3, $4, $5)
+        "INSERT INTO posts (author_id, book_id, title, content, content_html, reaction, rating, spoiler)
+        VALUES ($1, (SELECT id FROM books WHERE name = $2), $3, $4, $5, $6, $7, $8)
         RETURNING id",
         &claims.sub,
         &book,
         &title,
         &content,
-        &reaction as _
+        &markdown::render(&content),
+        &reaction as _,
+        &rating as _,
+        &spoiler.unwrap_or(false),
     )
     .fetch_one(&mut *transaction)
     .await
@@ -120,6 +143,7 @@ pub async fn create(
         }
         err => AppError::from(err),
     })?;
+    mentions::link_post(&mut transaction, pid, &content).await?;
     transaction.commit().await?;
     Ok(response(
         StatusCode::CREATED,
@@ -136,6 +160,9 @@ pub struct ReadQuery {
     comment_id: Option<i64>,
     #[validate(range(min = 0, message = "`previous_last` must point to a valid post!"))]
     previous_last: Option<i64>,
+    /// If specified, only posts that mention this user are fetched.
+    #[validate(length(min = 1, message = "`mentioned` must point to a valid user!"))]
+    mentioned: Option<String>,
 }
 
 #[instrument(name = "Reading a post", skip(pool, claims))]
@@ -146,6 +173,7 @@ pub async fn read(
         user,
         comment_id,
         previous_last,
+        mentioned,
     }): AppQuery<ReadQuery>,
 ) -> Result<Response, AppError> {
     let uid = claims.0.as_ref().map(|claims| claims.sub);
@@ -156,6 +184,10 @@ pub async fn read(
             p.id AS "id!",
             p.title AS "title!",
             p.content AS "content!", 
+            p.content_html AS "content_html?",
+            p.content_spans AS "content_spans!: _",
+            p.spoiler AS "spoiler!",
+            p.reveal_spoilers AS "reveal_spoilers!",
             p.author_name AS "author_name!",
             p.author_picture AS "author_picture?: _",
             b.title AS "book_title?",
@@ -164,6 +196,7 @@ pub async fn read(
             b.cover_image AS "book_cover?: _",
             b.spine_image AS "book_spine?: _",
             p.book_reaction AS "book_reaction!: _",
+            p.rating AS "rating?",
             p.reactions AS "reactions?: _",
             p.user_reaction AS "user_reaction!: _",
             coalesce(jsonb_agg(c) FILTER (WHERE c.id IS NOT NULL), '[]'::JSONB) AS "comments!: _"
@@ -188,15 +221,24 @@ pub async fn read(
             WHEN $4::BIGINT IS NULL THEN TRUE
             WHEN $4::BIGINT IS NOT NULL AND p.id < $4::BIGINT THEN TRUE
             ELSE FALSE
+        END AND CASE
+            WHEN $5::TEXT IS NULL THEN TRUE
+            WHEN $5::TEXT IS NOT NULL AND EXISTS (
+                SELECT FROM post_mentions pm JOIN users u ON u.id = pm.user_id
+                WHERE pm.post_id = p.id AND u.name = $5::TEXT
+            ) THEN TRUE
+            ELSE FALSE
         END
-        GROUP BY p.id, p.title, p.content, p.author_name, p.author_picture, b.title, b.name,
-        b.summary, b.cover_image, b.spine_image, p.book_reaction, p.reactions, p.user_reaction
+        GROUP BY p.id, p.title, p.content, p.content_html, p.content_spans, p.spoiler, p.reveal_spoilers,
+        p.author_name, p.author_picture, b.title, b.name, b.summary, b.cover_image,
+        b.spine_image, p.book_reaction, p.rating, p.reactions, p.user_reaction
         ORDER BY p.id DESC
         LIMIT 20"#,
         &uid as &_,
         &user as &_,
         &comment_id as &_,
         &previous_last as &_,
+        &mentioned as &_,
     )
     .fetch_all(&mut *transaction)
     .await?;
@@ -225,6 +267,10 @@ pub async fn read_slug(
             p.id AS "id!",
             p.title AS "title!",
             p.content AS "content!", 
+            p.content_html AS "content_html?",
+            p.content_spans AS "content_spans!: _",
+            p.spoiler AS "spoiler!",
+            p.reveal_spoilers AS "reveal_spoilers!",
             p.author_name AS "author_name!",
             p.author_picture AS "author_picture?: _",
             b.title AS "book_title?",
@@ -233,6 +279,7 @@ pub async fn read_slug(
             b.cover_image AS "book_cover?: _",
             b.spine_image AS "book_spine?: _",
             p.book_reaction AS "book_reaction!: _",
+            p.rating AS "rating?",
             p.reactions AS "reactions?: _",
             p.user_reaction AS "user_reaction!: _",
             coalesce(jsonb_agg(c) FILTER (WHERE c.id IS NOT NULL), '[]'::JSONB) AS "comments!: _"
@@ -250,8 +297,9 @@ pub async fn read_slug(
             LIMIT 20
         ) c ON TRUE
         WHERE p.id = $1
-        GROUP BY p.id, p.title, p.content, p.author_name, p.author_picture, b.title, b.name,
-        b.summary, b.cover_image, b.spine_image, p.book_reaction, p.reactions, p.user_reaction"#,
+        GROUP BY p.id, p.title, p.content, p.content_html, p.content_spans, p.spoiler, p.reveal_spoilers,
+        p.author_name, p.author_picture, b.title, b.name, b.summary, b.cover_image,
+        b.spine_image, p.book_reaction, p.rating, p.reactions, p.user_reaction"#,
         &post_id,
         &uid as &_,
         &comment_id as &_,
@@ -266,15 +314,67 @@ pub async fn read_slug(
     Ok(response(StatusCode::OK, None, AppJson(post)))
 }
 
+/// Reads the reviews getting the most reactions and comments lately, without
+/// their comments. Recent activity weighs more, see `trending_decay`.
+#[instrument(name = "Reading trending posts", skip(pool, claims))]
+pub async fn trending(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: OptionalUserClaims,
+    AppQuery(TrendingQuery { limit }): AppQuery<TrendingQuery>,
+) -> Result<Response, AppError> {
+    let uid = claims.0.as_ref().map(|claims| claims.sub);
+    let posts = sqlx::query_as!(
+        Post,
+        r#"SELECT
+            p.id AS "id!",
+            p.title AS "title!",
+            p.content AS "content!",
+            p.content_html AS "content_html?",
+            p.content_spans AS "content_spans!: _",
+            p.spoiler AS "spoiler!",
+            p.reveal_spoilers AS "reveal_spoilers!",
+            p.author_name AS "author_name!",
+            p.author_picture AS "author_picture?: _",
+            b.title AS "book_title?",
+            b.name AS "book_name?",
+            b.summary AS "book_synopsis?",
+            b.cover_image AS "book_cover?: _",
+            b.spine_image AS "book_spine?: _",
+            p.book_reaction AS "book_reaction!: _",
+            p.rating AS "rating?",
+            p.reactions AS "reactions?: _",
+            p.user_reaction AS "user_reaction!: _",
+            NULL::JSONB AS "comments?: _"
+        FROM trending_posts tp
+        JOIN fetch_posts(request_uid => $1) p
+        ON p.id = tp.post_id
+        JOIN books_view b
+        ON b.id = p.book_id
+        WHERE tp.score > 0
+        ORDER BY tp.score DESC, p.id DESC
+        LIMIT $2"#,
+        &uid as &_,
+        limit.unwrap_or(20),
+    )
+    .fetch_all(&pool)
+    .await?;
+    Ok(response(StatusCode::OK, None, AppJson(posts)))
+}
+
 #[derive(serde::Deserialize, Validate)]
 pub struct UpdatePayload {
     #[validate(range(min = 0))]
     id: i64,
     #[validate(length(min = 1))]
     title: Option<String>,
-    #[validate(length(min = 1))]
+    #[validate(length(min = 1, max = MAX_POST_CONTENT_LENGTH))]
     content: Option<String>,
     reaction: Option<Reaction>,
+    /// Removed when `null`.
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(custom(function = "rating_is_valid"))]
+    rating: Option<Option<f32>>,
+    spoiler: Option<bool>,
 }
 
 #[instrument(name = "Updating a post", skip(pool, claims, title, content, reaction), fields(uid = %claims.sub))]
@@ -286,11 +386,13 @@ pub async fn update(
         title,
         content,
         reaction,
+        rating,
+        spoiler,
     }): AppJson<UpdatePayload>,
 ) -> Result<impl IntoResponse, AppError> {
     let mut transaction = pool.begin().await?;
     let post = sqlx::query!(
-        r#"SELECT title, content, reaction AS "reaction: Reaction" FROM posts WHERE id = $1 AND author_id = $2"#,
+        r#"SELECT title, content, reaction AS "reaction: Reaction", rating, spoiler FROM posts WHERE id = $1 AND author_id = $2"#,
         &id,
         &claims.sub
     )
@@ -300,19 +402,25 @@ pub async fn update(
         sqlx::Error::RowNotFound => AppError::from(PostsError::UpdateUnauthorized(id)),
         _ => AppError::from(err),
     })?;
+    let content = content.unwrap_or(post.content);
     let update_result = sqlx::query!(
-        "UPDATE posts SET title = $3, content = $4, reaction = $5 WHERE id = $1 AND author_id = $2",
+        "UPDATE posts SET title = $3, content = $4, content_html = $5, reaction = $6, rating = $7, spoiler = $8
+        WHERE id = $1 AND author_id = $2",
         &id,
         &claims.sub,
         &title.unwrap_or_else(|| post.title),
-        &content.unwrap_or_else(|| post.content),
+        &content,
+        &markdown::render(&content),
         &reaction.unwrap_or_else(|| post.reaction) as &_,
+        &rating.unwrap_or(post.rating) as &_,
+        &spoiler.unwrap_or(post.spoiler),
     )
     .execute(&mut *transaction)
     .await?;
     if update_result.rows_affected() == 0 {
         return Err(PostsError::UpdateUnauthorized(id))?;
     }
+    mentions::link_post(&mut transaction, id, &content).await?;
     transaction.commit().await?;
     Ok(StatusCode::NO_CONTENT)
 }
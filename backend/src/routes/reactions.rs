@@ -1,4 +1,7 @@
-use super::auth::UserClaims;
+use super::{
+    auth::UserClaims,
+    users::{UserError, USER_BLOCKED_ERRCODE},
+};
 use crate::{
     app::AppState,
     utils::{errors::AppError, response::response, structs::AppJson},
@@ -81,7 +84,15 @@ pub async fn create(
             &post_id
         ),
     };
-    insert_query.execute(&mut *transaction).await?;
+    insert_query
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref err) if err.code().as_deref() == Some(USER_BLOCKED_ERRCODE) => {
+                AppError::from(UserError::Blocked)
+            }
+            err => AppError::from(err),
+        })?;
     transaction.commit().await?;
     Ok(response(
         StatusCode::CREATED,
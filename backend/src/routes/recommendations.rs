@@ -0,0 +1,145 @@
+use super::{
+    auth::UserClaims,
+    books::{BookAuthor, BookCategory, BookReactionMetadata, BooksError},
+};
+use crate::{
+    app::AppState,
+    jobs::recommendations::{cache_key, Target},
+    settings::SETTINGS,
+    utils::{
+        errors::AppError,
+        response::response,
+        structs::{AppImage, AppJson},
+    },
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Response,
+};
+use redis::Commands;
+use tracing::{event, instrument, Level};
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Recommendation {
+    title: String,
+    name: String,
+    summary: String,
+    language: String,
+    cover_image: Option<sqlx::types::Json<AppImage>>,
+    spine_image: Option<sqlx::types::Json<AppImage>>,
+    authors: sqlx::types::Json<Vec<BookAuthor>>,
+    categories: sqlx::types::Json<Vec<BookCategory>>,
+    reactions: Option<sqlx::types::Json<BookReactionMetadata>>,
+    /// How strongly the book is recommended. Only comparable between
+    /// the recommendations of the same book or user.
+    score: f64,
+}
+
+/// Returns the recommendations cached for `target`. Failing to reach Redis
+/// is not fatal, as they can be read from the database instead.
+fn read_cache(redis_client: &redis::Client, target: Target) -> Option<Vec<Recommendation>> {
+    let cached = redis_client
+        .get_connection()
+        .and_then(|mut redis_con| redis_con.get::<_, Option<String>>(cache_key(target)));
+    match cached {
+        Ok(cached) => cached.and_then(|cached| serde_json::from_str(&cached).ok()),
+        Err(err) => {
+            event!(Level::WARN, error = %err, "failed to read cached recommendations");
+            None
+        }
+    }
+}
+
+fn write_cache(redis_client: &redis::Client, target: Target, recommendations: &[Recommendation]) {
+    let Ok(serialized) = serde_json::to_string(recommendations) else {
+        return;
+    };
+    let written = redis_client.get_connection().and_then(|mut redis_con| {
+        redis_con.set_ex::<_, _, ()>(cache_key(target), serialized, SETTINGS.recommendations.ttl)
+    });
+    if let Err(err) = written {
+        event!(Level::WARN, error = %err, "failed to cache recommendations");
+    }
+}
+
+/// Reads the books that readers of the book `slug` might also like.
+#[instrument(name = "Reading a book's recommendations", skip(pool, redis_client))]
+pub async fn read_book(
+    State(AppState {
+        pool, redis_client, ..
+    }): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<Response, AppError> {
+    let book_id = sqlx::query_scalar!("SELECT id FROM books WHERE name = $1", &slug)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or(BooksError::BookNotFound(slug))?;
+    let target = Target::Book(book_id);
+    if let Some(recommendations) = read_cache(&redis_client, target) {
+        return Ok(response(StatusCode::OK, None, AppJson(recommendations)));
+    }
+
+    let recommendations = sqlx::query_as!(
+        Recommendation,
+        r#"SELECT
+            b.title AS "title!",
+            b.name AS "name!",
+            b.summary AS "summary!",
+            b.lang AS "language!",
+            b.cover_image AS "cover_image?: _",
+            b.spine_image AS "spine_image?: _",
+            b.authors AS "authors!: _",
+            b.categories AS "categories!: _",
+            b.reactions AS "reactions?: _",
+            br.score
+        FROM book_recommendations br
+        JOIN books_view b ON b.id = br.recommended_id
+        WHERE br.book_id = $1
+        ORDER BY br.score DESC, b.id DESC"#,
+        book_id,
+    )
+    .fetch_all(&pool)
+    .await?;
+    write_cache(&redis_client, target, &recommendations);
+    Ok(response(StatusCode::OK, None, AppJson(recommendations)))
+}
+
+/// Reads the books the requester might like, based on those they read or
+/// liked. It is empty until recommendations are next refreshed after that.
+#[instrument(name = "Reading an user's recommendations", skip(pool, redis_client, claims), fields(uid = %claims.sub))]
+pub async fn read_user(
+    State(AppState {
+        pool, redis_client, ..
+    }): State<AppState>,
+    claims: UserClaims,
+) -> Result<Response, AppError> {
+    let target = Target::User(claims.sub);
+    if let Some(recommendations) = read_cache(&redis_client, target) {
+        return Ok(response(StatusCode::OK, None, AppJson(recommendations)));
+    }
+
+    let recommendations = sqlx::query_as!(
+        Recommendation,
+        r#"SELECT
+            b.title AS "title!",
+            b.name AS "name!",
+            b.summary AS "summary!",
+            b.lang AS "language!",
+            b.cover_image AS "cover_image?: _",
+            b.spine_image AS "spine_image?: _",
+            b.authors AS "authors!: _",
+            b.categories AS "categories!: _",
+            b.reactions AS "reactions?: _",
+            ur.score
+        FROM user_recommendations ur
+        JOIN books_view b ON b.id = ur.book_id
+        WHERE ur.user_id = $1
+        ORDER BY ur.score DESC, b.id DESC"#,
+        &claims.sub,
+    )
+    .fetch_all(&pool)
+    .await?;
+    write_cache(&redis_client, target, &recommendations);
+    Ok(response(StatusCode::OK, None, AppJson(recommendations)))
+}
@@ -0,0 +1,294 @@
+use super::auth::OptionalUserClaims;
+use crate::{
+    app::AppState,
+    utils::{
+        errors::AppError,
+        response::response,
+        structs::{AppImage, AppJson, AppQuery},
+    },
+};
+use axum::{extract::State, http::StatusCode, response::Response};
+use tracing::instrument;
+use validator::{Validate, ValidationError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchKind {
+    Book,
+    Post,
+    Comment,
+    User,
+}
+
+#[derive(serde::Deserialize, Validate)]
+#[validate(schema(function = "validate_search_query"))]
+pub struct SearchQuery {
+    #[validate(length(min = 1, max = 200, message = "Query is either too short or too long!"))]
+    q: String,
+    /// Only fetch results of this kind. Facets are always counted for every kind.
+    kind: Option<SearchKind>,
+    #[validate(range(min = 0, message = "`previous_last` must point to a valid result!"))]
+    previous_last: Option<i64>,
+}
+
+fn validate_search_query(query: &SearchQuery) -> Result<(), ValidationError> {
+    if query.previous_last.is_some() && query.kind.is_none() {
+        return Err(ValidationError::new(
+            "`previous_last` can only be used together with `kind`.",
+        ));
+    }
+    Ok(())
+}
+
+/// How many results of each kind match the query.
+#[derive(serde::Serialize)]
+pub struct SearchFacets {
+    book: i64,
+    post: i64,
+    comment: i64,
+    user: i64,
+}
+#[derive(serde::Serialize)]
+pub struct BookHit {
+    id: i64,
+    title: String,
+    name: String,
+    /// The summary, with matches wrapped in `<mark>` tags.
+    snippet: String,
+}
+#[derive(serde::Serialize)]
+pub struct PostHit {
+    id: i64,
+    title: String,
+    /// The content outside of spoilers, with matches wrapped in `<mark>` tags.
+    snippet: String,
+    author_name: String,
+    book_title: String,
+    book_name: String,
+}
+#[derive(serde::Serialize)]
+pub struct CommentHit {
+    id: i64,
+    post_id: i64,
+    /// The content outside of spoilers, with matches wrapped in `<mark>` tags.
+    snippet: String,
+    author_name: String,
+}
+#[derive(serde::Serialize)]
+pub struct UserHit {
+    id: i64,
+    name: String,
+    picture: Option<sqlx::types::Json<AppImage>>,
+}
+#[derive(serde::Serialize)]
+pub struct SearchResponse {
+    facets: SearchFacets,
+    books: Vec<BookHit>,
+    posts: Vec<PostHit>,
+    comments: Vec<CommentHit>,
+    users: Vec<UserHit>,
+}
+
+/// Searches books, reviews, comments and users at once. Books are searched
+/// in their own language, and everything else in English. Each kind of result
+/// is paginated on its own, newest first, so `previous_last` requires `kind`.
+/// Content hidden from the requester, such as that of banned or restricted
+/// users, is neither returned nor counted, and neither are comments under
+/// their posts.
+#[instrument(name = "Searching...", skip(pool, claims))]
+pub async fn search(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: OptionalUserClaims,
+    AppQuery(SearchQuery {
+        q,
+        kind,
+        previous_last,
+    }): AppQuery<SearchQuery>,
+) -> Result<Response, AppError> {
+    let uid = claims.0.as_ref().map(|claims| claims.sub);
+    let includes = |other: SearchKind| kind.is_none_or(|kind| kind == other);
+    let mut transaction = pool.begin().await?;
+
+    let facets = sqlx::query_as!(
+        SearchFacets,
+        r#"SELECT
+            (
+                SELECT count(*) FROM books b
+                JOIN book_languages bl ON bl.code = b.language
+                WHERE b.text_search @@ websearch_to_tsquery(bl.ts_config, $2)
+            ) AS "book!",
+            (
+                SELECT count(*) FROM posts p
+                JOIN users u ON u.id = p.author_id
+                WHERE p.text_search @@ query.content
+                AND NOT u.is_banned AND NOT is_restricted_by($1, u.id)
+            ) AS "post!",
+            (
+                SELECT count(*) FROM comments c
+                JOIN users u ON u.id = c.author_id
+                JOIN posts p ON p.id = c.post_id
+                JOIN users pu ON pu.id = p.author_id
+                WHERE c.text_search @@ query.content
+                AND NOT u.is_banned AND NOT is_restricted_by($1, u.id)
+                AND NOT pu.is_banned AND NOT is_restricted_by($1, pu.id)
+            ) AS "comment!",
+            (
+                SELECT count(*) FROM users u
+                WHERE u.text_search @@ query.names
+                AND NOT u.is_banned AND NOT is_restricted_by($1, u.id)
+            ) AS "user!"
+        FROM (
+            SELECT
+                websearch_to_tsquery('english', $2) AS content,
+                websearch_to_tsquery('simple', $2) AS names
+        ) query"#,
+        &uid as &_,
+        &q,
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    let books = if includes(SearchKind::Book) {
+        sqlx::query_as!(
+            BookHit,
+            r#"SELECT
+                b.id,
+                b.title,
+                b.name,
+                search_headline(bl.ts_config, b.summary, query.query) AS "snippet!"
+            FROM books b
+            JOIN book_languages bl ON bl.code = b.language
+            JOIN LATERAL (
+                SELECT websearch_to_tsquery(bl.ts_config, $1) AS query
+            ) query ON TRUE
+            WHERE b.text_search @@ query.query AND CASE
+                WHEN $2::BIGINT IS NULL THEN TRUE
+                WHEN $2::BIGINT IS NOT NULL AND b.id < $2::BIGINT THEN TRUE
+                ELSE FALSE
+            END
+            ORDER BY b.id DESC
+            LIMIT 20"#,
+            &q,
+            &previous_last as &_,
+        )
+        .fetch_all(&mut *transaction)
+        .await?
+    } else {
+        Vec::new()
+    };
+
+    let posts = if includes(SearchKind::Post) {
+        sqlx::query_as!(
+            PostHit,
+            r#"SELECT
+                p.id,
+                p.title,
+                search_headline('english', CASE
+                    WHEN p.spoiler THEN ''
+                    ELSE coalesce(strip_spoilers(p.content), '')
+                END, query) AS "snippet!",
+                u.name AS author_name,
+                b.title AS book_title,
+                b.name AS book_name
+            FROM posts p
+            JOIN users u ON u.id = p.author_id
+            JOIN books b ON b.id = p.book_id,
+            websearch_to_tsquery('english', $2) query
+            WHERE p.text_search @@ query
+            AND NOT u.is_banned AND NOT is_restricted_by($1, u.id)
+            AND CASE
+                WHEN $3::BIGINT IS NULL THEN TRUE
+                WHEN $3::BIGINT IS NOT NULL AND p.id < $3::BIGINT THEN TRUE
+                ELSE FALSE
+            END
+            ORDER BY p.id DESC
+            LIMIT 20"#,
+            &uid as &_,
+            &q,
+            &previous_last as &_,
+        )
+        .fetch_all(&mut *transaction)
+        .await?
+    } else {
+        Vec::new()
+    };
+
+    let comments = if includes(SearchKind::Comment) {
+        sqlx::query_as!(
+            CommentHit,
+            r#"SELECT
+                c.id,
+                c.post_id,
+                search_headline(
+                    'english',
+                    coalesce(strip_spoilers(c.content), ''),
+                    query
+                ) AS "snippet!",
+                u.name AS author_name
+            FROM comments c
+            JOIN users u ON u.id = c.author_id
+            JOIN posts p ON p.id = c.post_id
+            JOIN users pu ON pu.id = p.author_id,
+            websearch_to_tsquery('english', $2) query
+            WHERE c.text_search @@ query
+            AND NOT u.is_banned AND NOT is_restricted_by($1, u.id)
+            AND NOT pu.is_banned AND NOT is_restricted_by($1, pu.id)
+            AND CASE
+                WHEN $3::BIGINT IS NULL THEN TRUE
+                WHEN $3::BIGINT IS NOT NULL AND c.id < $3::BIGINT THEN TRUE
+                ELSE FALSE
+            END
+            ORDER BY c.id DESC
+            LIMIT 20"#,
+            &uid as &_,
+            &q,
+            &previous_last as &_,
+        )
+        .fetch_all(&mut *transaction)
+        .await?
+    } else {
+        Vec::new()
+    };
+
+    let users = if includes(SearchKind::User) {
+        sqlx::query_as!(
+            UserHit,
+            r#"SELECT
+                u.id AS "id!",
+                u.name AS "name!",
+                u.picture AS "picture?: _"
+            FROM users_view u
+            JOIN users us ON us.id = u.id,
+            websearch_to_tsquery('simple', $2) query
+            WHERE us.text_search @@ query
+            AND NOT u.is_banned AND NOT is_restricted_by($1, u.id)
+            AND CASE
+                WHEN $3::BIGINT IS NULL THEN TRUE
+                WHEN $3::BIGINT IS NOT NULL AND u.id < $3::BIGINT THEN TRUE
+                ELSE FALSE
+            END
+            ORDER BY u.id DESC
+            LIMIT 20"#,
+            &uid as &_,
+            &q,
+            &previous_last as &_,
+        )
+        .fetch_all(&mut *transaction)
+        .await?
+    } else {
+        Vec::new()
+    };
+    transaction.commit().await?;
+
+    Ok(response(
+        StatusCode::OK,
+        None,
+        AppJson(SearchResponse {
+            facets,
+            books,
+            posts,
+            comments,
+            users,
+        }),
+    ))
+}
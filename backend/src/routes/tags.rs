@@ -0,0 +1,77 @@
+use super::{auth::OptionalUserClaims, posts::Post};
+use crate::{
+    app::AppState,
+    utils::{
+        errors::AppError,
+        response::response,
+        structs::{AppJson, AppQuery},
+    },
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Response,
+};
+use tracing::instrument;
+use validator::Validate;
+
+#[derive(serde::Deserialize, Validate)]
+pub struct ReadQuery {
+    #[validate(range(min = 0, message = "`previous_last` must point to a valid post!"))]
+    previous_last: Option<i64>,
+}
+
+#[instrument(name = "Reading posts with a tag", skip(pool, claims))]
+pub async fn read(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: OptionalUserClaims,
+    Path(tag): Path<String>,
+    AppQuery(ReadQuery { previous_last }): AppQuery<ReadQuery>,
+) -> Result<Response, AppError> {
+    let uid = claims.0.as_ref().map(|claims| claims.sub);
+    let mut transaction = pool.begin().await?;
+    let posts = sqlx::query_as!(
+        Post,
+        r#"SELECT
+            p.id AS "id!",
+            p.title AS "title!",
+            p.content AS "content!",
+            p.content_html AS "content_html?",
+            p.content_spans AS "content_spans!: _",
+            p.spoiler AS "spoiler!",
+            p.reveal_spoilers AS "reveal_spoilers!",
+            p.author_name AS "author_name!",
+            p.author_picture AS "author_picture?: _",
+            b.title AS "book_title?",
+            b.name AS "book_name?",
+            b.summary AS "book_synopsis?",
+            b.cover_image AS "book_cover?: _",
+            b.spine_image AS "book_spine?: _",
+            p.book_reaction AS "book_reaction!: _",
+            p.rating AS "rating?",
+            p.reactions AS "reactions?: _",
+            p.user_reaction AS "user_reaction!: _",
+            NULL::JSONB AS "comments?: _"
+        FROM fetch_posts(request_uid => $1) p
+        JOIN books_view b
+        ON b.id = p.book_id
+        JOIN post_tags pt
+        ON pt.post_id = p.id
+        JOIN tags t
+        ON t.id = pt.tag_id
+        WHERE t.name = LOWER($2) AND CASE
+            WHEN $3::BIGINT IS NULL THEN TRUE
+            WHEN $3::BIGINT IS NOT NULL AND p.id < $3::BIGINT THEN TRUE
+            ELSE FALSE
+        END
+        ORDER BY p.id DESC
+        LIMIT 20"#,
+        &uid as &_,
+        &tag,
+        &previous_last as &_,
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(response(StatusCode::OK, None, AppJson(posts)))
+}
@@ -1,28 +1,58 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::Response,
+    response::{IntoResponse, Response},
 };
+use chrono::{DateTime, Utc};
+use tracing::{event, instrument, Level};
+use validator::{Validate, ValidationError};
 
 use crate::{
     app::AppState,
+    settings::SETTINGS,
     utils::{
+        constants::TEMPLATES,
+        emails::send_email,
         errors::AppError,
         response::response,
         structs::{AppImage, AppJson},
     },
 };
 
-use super::books::Book;
+use super::{
+    auth::{AdminClaims, UserClaims},
+    books::Book,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum UserError {
     #[error("user {0} cannot be found")]
     UserNotFound(String),
+    #[error("user has been blocked by the author of the content they tried to interact with")]
+    Blocked,
+    #[error("user tried to block or mute themselves")]
+    CannotRestrictSelf,
+    #[error("user tried to mute an user they blocked")]
+    AlreadyBlocked,
     #[error("this error is not expected")]
     Unexpected,
 }
 
+/// The SQLSTATE raised by the database when a blocked user
+/// tries to interact with the blocker's content.
+pub static USER_BLOCKED_ERRCODE: &str = "BL001";
+
+#[derive(Clone, Debug, PartialEq, PartialOrd, sqlx::Type, serde::Deserialize, serde::Serialize)]
+#[sqlx(type_name = "urestrict", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum UserRestriction {
+    /// Hides the target's content, and prevents them from
+    /// replying or reacting to the user's content.
+    Block,
+    /// Only hides the target's content.
+    Mute,
+}
+
 #[derive(serde::Serialize)]
 pub struct UserMetadata {
     pub name: String,
@@ -60,11 +90,346 @@ pub async fn read_metadata(
             WHERE ub.user_id = u.id AND ub.completed = TRUE
             ORDER BY ub.ends_at DESC
         ) b ON TRUE
-        WHERE u.name = $1
+        WHERE u.name = $1 AND NOT u.is_banned
         GROUP BY u.name, u.picture"#,
         &user,
     )
     .fetch_one(&mut *tx)
-    .await?;
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => AppError::from(UserError::UserNotFound(user)),
+        _ => AppError::from(e),
+    })?;
     Ok(response(StatusCode::OK, None, AppJson(metadata)))
 }
+
+#[instrument(
+    name = "Sending a moderation email",
+    skip(recipient_name, recipient_email, reason),
+    fields(recipient_name = %recipient_name)
+)]
+async fn send_moderation_email(
+    subject: &str,
+    message: &str,
+    recipient_name: String,
+    recipient_email: String,
+    reason: Option<String>,
+    until: Option<DateTime<Utc>>,
+) -> Result<(), AppError> {
+    let template = TEMPLATES.get_template("moderation_email.html")?;
+    // Sat, 01 Jun 2024 14:17:00 UTC+0000
+    let until = until.map(|until| until.format("%a, %b %d %Y %X UTC%z").to_string());
+    let ctx = minijinja::context! {
+        title => subject,
+        name => &recipient_name,
+        message => message,
+        reason => &reason,
+        until => &until,
+        domain => &SETTINGS.frontend.url,
+    };
+    let html_text = template.render(ctx)?;
+    let mut text = format!("{}\n", message);
+    if let Some(reason) = &reason {
+        text.push_str(&format!("Reason: {}\n", reason));
+    }
+    if let Some(until) = &until {
+        text.push_str(&format!("Your account will be usable again at {}.\n", until));
+    }
+    send_email(
+        None,
+        recipient_name,
+        recipient_email,
+        subject,
+        html_text,
+        text,
+    )
+    .await
+}
+
+#[derive(serde::Deserialize, Validate)]
+#[validate(schema(function = "validate_ban_payload"))]
+pub struct BanPayload {
+    /// When specified, the user is only suspended until this
+    /// point in time. Otherwise, the user is banned permanently.
+    until: Option<DateTime<Utc>>,
+    #[validate(length(min = 1, max = 1000, message = "Reason is either too short or too long!"))]
+    reason: String,
+}
+
+fn validate_ban_payload(payload: &BanPayload) -> Result<(), ValidationError> {
+    if payload.until.is_some_and(|until| until <= Utc::now()) {
+        return Err(ValidationError::new(
+            "A suspension must end in the future.",
+        ));
+    }
+    Ok(())
+}
+
+/// Bans or suspends an user. Administrators cannot be banned.
+#[instrument(name = "Banning an user", skip(pool, claims, reason), fields(uid = %claims.sub))]
+pub async fn ban(
+    State(AppState { pool, .. }): State<AppState>,
+    AdminClaims(claims): AdminClaims,
+    Path(user): Path<String>,
+    AppJson(BanPayload { until, reason }): AppJson<BanPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = pool.begin().await?;
+    let target = sqlx::query!(
+        "UPDATE users SET is_banned = $2, suspended_until = $3, ban_reason = $4
+        WHERE name = $1 AND role = 'user'
+        RETURNING name, email",
+        &user,
+        until.is_none(),
+        until as _,
+        &reason,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => AppError::from(UserError::UserNotFound(user)),
+        _ => AppError::from(e),
+    })?;
+    tx.commit().await?;
+    let (subject, message) = match until {
+        Some(_) => (
+            "blisk - Your account has been suspended",
+            "Your blisk account has been temporarily suspended. You will not be able to sign in until the suspension ends.",
+        ),
+        None => (
+            "blisk - Your account has been banned",
+            "Your blisk account has been permanently banned. Your reviews and comments are no longer visible to other readers.",
+        ),
+    };
+    // The ban already took effect, so failing to tell the user is only logged.
+    if let Err(err) = send_moderation_email(
+        subject,
+        message,
+        target.name,
+        target.email,
+        Some(reason),
+        until,
+    )
+    .await
+    {
+        event!(Level::ERROR, error = %err, "failed to send the ban email");
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lifts a ban or a suspension.
+#[instrument(name = "Unbanning an user", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn unban(
+    State(AppState { pool, .. }): State<AppState>,
+    AdminClaims(claims): AdminClaims,
+    Path(user): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = pool.begin().await?;
+    let target = sqlx::query!(
+        r#"WITH old AS (SELECT id, is_banned, suspended_until FROM users WHERE name = $1)
+        UPDATE users u SET is_banned = FALSE, suspended_until = NULL, ban_reason = NULL
+        FROM old
+        WHERE u.id = old.id
+        RETURNING u.name, u.email, (old.is_banned OR old.suspended_until IS NOT NULL) AS "was_restricted!""#,
+        &user,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => AppError::from(UserError::UserNotFound(user)),
+        _ => AppError::from(e),
+    })?;
+    tx.commit().await?;
+    if !target.was_restricted {
+        return Ok(StatusCode::NO_CONTENT);
+    }
+    if let Err(err) = send_moderation_email(
+        "blisk - Your account has been restored",
+        "The restrictions on your blisk account have been lifted. You can sign in again.",
+        target.name,
+        target.email,
+        None,
+        None,
+    )
+    .await
+    {
+        event!(Level::ERROR, error = %err, "failed to send the unban email");
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(serde::Serialize)]
+pub struct RestrictedUser {
+    pub name: String,
+    pub picture: Option<sqlx::types::Json<AppImage>>,
+    pub restriction: UserRestriction,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ReadRestrictionsQuery {
+    /// Only list users with this kind of restriction.
+    restriction: Option<UserRestriction>,
+}
+
+#[instrument(name = "Reading an user's restrictions", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn read_restrictions(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: UserClaims,
+    Query(ReadRestrictionsQuery { restriction }): Query<ReadRestrictionsQuery>,
+) -> Result<Response, AppError> {
+    let mut tx = pool.begin().await?;
+    let users = sqlx::query_as!(
+        RestrictedUser,
+        r#"SELECT
+            u.name AS "name!",
+            u.picture AS "picture?: _",
+            ur.type AS "restriction!: _"
+        FROM user_restrictions ur
+        JOIN users_view u ON u.id = ur.target_id
+        WHERE ur.user_id = $1 AND ($2::URESTRICT IS NULL OR ur.type = $2)
+        ORDER BY u.name"#,
+        &claims.sub,
+        &restriction as &_,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(response(StatusCode::OK, None, AppJson(users)))
+}
+
+async fn restrict(
+    pool: &sqlx::PgPool,
+    uid: i64,
+    target: String,
+    restriction: UserRestriction,
+) -> Result<StatusCode, AppError> {
+    let mut tx = pool.begin().await?;
+    let target_id = sqlx::query_scalar!("SELECT id FROM users WHERE name = $1", &target)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::from(UserError::UserNotFound(target)),
+            _ => AppError::from(e),
+        })?;
+    if target_id == uid {
+        return Err(UserError::CannotRestrictSelf.into());
+    }
+    // Blocking an user replaces muting them, but a block is never
+    // downgraded to a mute.
+    sqlx::query_scalar!(
+        r#"INSERT INTO user_restrictions ("type", user_id, target_id) VALUES ($1, $2, $3)
+        ON CONFLICT (user_id, target_id) DO UPDATE SET "type" = EXCLUDED.type
+        WHERE user_restrictions.type <> 'block' OR EXCLUDED.type = 'block'
+        RETURNING 1"#,
+        &restriction as _,
+        &uid,
+        &target_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(UserError::AlreadyBlocked)?;
+    tx.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn lift_restriction(
+    pool: &sqlx::PgPool,
+    uid: i64,
+    target: String,
+    restriction: UserRestriction,
+) -> Result<StatusCode, AppError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"DELETE FROM user_restrictions ur
+        USING users u
+        WHERE u.id = ur.target_id AND ur.user_id = $1 AND u.name = $2 AND ur.type = $3"#,
+        &uid,
+        &target,
+        &restriction as _,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[instrument(name = "Blocking an user", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn block(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: UserClaims,
+    Path(user): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    restrict(&pool, claims.sub, user, UserRestriction::Block).await
+}
+
+#[instrument(name = "Unblocking an user", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn unblock(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: UserClaims,
+    Path(user): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    lift_restriction(&pool, claims.sub, user, UserRestriction::Block).await
+}
+
+#[instrument(name = "Muting an user", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn mute(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: UserClaims,
+    Path(user): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    restrict(&pool, claims.sub, user, UserRestriction::Mute).await
+}
+
+#[instrument(name = "Unmuting an user", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn unmute(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: UserClaims,
+    Path(user): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    lift_restriction(&pool, claims.sub, user, UserRestriction::Mute).await
+}
+
+#[derive(serde::Serialize)]
+pub struct UserPreferences {
+    /// Whether spoilers of books the user has finished should be shown as-is.
+    reveal_spoilers: bool,
+}
+
+#[instrument(name = "Reading an user's preferences", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn read_preferences(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: UserClaims,
+) -> Result<Response, AppError> {
+    let mut tx = pool.begin().await?;
+    let preferences = sqlx::query_as!(
+        UserPreferences,
+        "SELECT reveal_spoilers FROM users WHERE id = $1",
+        &claims.sub
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(response(StatusCode::OK, None, AppJson(preferences)))
+}
+
+#[derive(serde::Deserialize, Validate)]
+pub struct UpdatePreferencesPayload {
+    reveal_spoilers: Option<bool>,
+}
+
+#[instrument(name = "Updating an user's preferences", skip(pool, claims), fields(uid = %claims.sub))]
+pub async fn update_preferences(
+    State(AppState { pool, .. }): State<AppState>,
+    claims: UserClaims,
+    AppJson(UpdatePreferencesPayload { reveal_spoilers }): AppJson<UpdatePreferencesPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        "UPDATE users SET reveal_spoilers = coalesce($2, reveal_spoilers) WHERE id = $1",
+        &claims.sub,
+        reveal_spoilers as _,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
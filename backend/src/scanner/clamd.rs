@@ -0,0 +1,55 @@
+use super::{Scanner, ScannerError, Verdict};
+use axum::async_trait;
+use std::path::PathBuf;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+
+/// The largest chunk sent at once. clamd only bounds the size of the
+/// whole stream, through its `StreamMaxLength` option.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Talks to a ClamAV daemon listening on a local socket.
+pub struct ClamdScanner {
+    socket: PathBuf,
+}
+
+impl ClamdScanner {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Scanner for ClamdScanner {
+    /// Streams the file with the `INSTREAM` command. Each chunk is prefixed
+    /// with its length, and an empty chunk ends the stream.
+    async fn scan(&self, bytes: &[u8]) -> Result<Verdict, ScannerError> {
+        let mut stream = UnixStream::connect(&self.socket).await?;
+        stream.write_all(b"zINSTREAM\0").await?;
+        for chunk in bytes.chunks(CHUNK_SIZE) {
+            stream
+                .write_all(&(chunk.len() as u32).to_be_bytes())
+                .await?;
+            stream.write_all(chunk).await?;
+        }
+        stream.write_all(&0u32.to_be_bytes()).await?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        let response = String::from_utf8_lossy(&response);
+        let response = response.trim_end_matches('\0').trim();
+        // Replies look like `stream: OK` or `stream: Eicar-Signature FOUND`.
+        match response.strip_prefix("stream: ") {
+            Some("OK") => Ok(Verdict::Clean),
+            Some(result) => match result.strip_suffix(" FOUND") {
+                Some(threat) => Ok(Verdict::Infected(threat.to_owned())),
+                None => Err(ScannerError::Protocol(response.to_owned())),
+            },
+            None => Err(ScannerError::Protocol(response.to_owned())),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+pub mod clamd;
+pub mod noop;
+
+use crate::settings::{ScannerKind, ScannerSettings};
+use axum::async_trait;
+use std::sync::Arc;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScannerError {
+    #[error("received an IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("received an unexpected response from the scanner: {0}")]
+    Protocol(String),
+}
+
+/// What a scanner found in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Clean,
+    /// The file is malicious. Holds the name of the threat that was found.
+    Infected(String),
+}
+
+/// Checks uploaded files for malware before they are stored.
+#[async_trait]
+pub trait Scanner: Send + Sync {
+    /// Scans the content of a file.
+    async fn scan(&self, bytes: &[u8]) -> Result<Verdict, ScannerError>;
+}
+
+/// Creates the scanner selected in `settings`.
+pub fn from_settings(settings: &ScannerSettings) -> Arc<dyn Scanner> {
+    match settings.backend {
+        ScannerKind::Noop => Arc::new(noop::NoopScanner),
+        ScannerKind::Clamd => Arc::new(clamd::ClamdScanner::new(&settings.socket)),
+    }
+}
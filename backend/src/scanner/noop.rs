@@ -0,0 +1,12 @@
+use super::{Scanner, ScannerError, Verdict};
+use axum::async_trait;
+
+/// Considers every file clean, for deployments without a scanner.
+pub struct NoopScanner;
+
+#[async_trait]
+impl Scanner for NoopScanner {
+    async fn scan(&self, _bytes: &[u8]) -> Result<Verdict, ScannerError> {
+        Ok(Verdict::Clean)
+    }
+}
@@ -32,6 +32,118 @@ pub struct RedisSettings {
     pub uri: String,
 }
 #[derive(serde::Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageKind {
+    S3,
+    Local,
+    Memory,
+    #[cfg(feature = "hdfs")]
+    Hdfs,
+}
+#[cfg(feature = "hdfs")]
+#[derive(serde::Deserialize, Clone)]
+pub struct HdfsSettings {
+    /// The address of the name node, e.g. `hdfs://localhost:9000`.
+    pub name_node: String,
+    /// The user to connect as.
+    pub user: String,
+}
+#[derive(serde::Deserialize, Clone)]
+pub struct PresignSettings {
+    /// How long a presigned upload stays valid, in seconds.
+    pub exp: u64,
+}
+#[derive(serde::Deserialize, Clone)]
+pub struct LimitSettings {
+    /// How many bytes each user can store, variants included.
+    pub quota: u64,
+    /// The largest file that can be uploaded, in bytes.
+    pub size: u64,
+    /// The MIME types of the files that can be uploaded.
+    pub types: Vec<String>,
+}
+#[derive(serde::Deserialize, Clone)]
+pub struct SignSettings {
+    /// How long a signed URL stays valid, in seconds.
+    pub exp: i64,
+}
+#[derive(serde::Deserialize, Clone)]
+pub struct GcSettings {
+    /// How often orphaned files are collected, in seconds.
+    pub interval: u64,
+    /// How long a file must have been orphaned before it is deleted, in seconds.
+    pub grace: u64,
+    /// Whether orphaned files are only reported instead of being deleted.
+    pub dry: bool,
+}
+#[derive(serde::Deserialize, Clone)]
+pub struct StorageSettings {
+    /// Where uploaded files are stored.
+    pub backend: StorageKind,
+    /// The bucket used by the `s3` backend.
+    pub bucket: String,
+    /// The directory used by the `local` and `hdfs` backends.
+    pub root: String,
+    /// Limits on what users can upload.
+    pub limits: LimitSettings,
+    /// Uploads sent directly to the backend.
+    pub presign: PresignSettings,
+    /// URLs that grant access to private files.
+    pub sign: SignSettings,
+    /// Garbage collection of orphaned files.
+    pub gc: GcSettings,
+    /// The cluster used by the `hdfs` backend.
+    #[cfg(feature = "hdfs")]
+    pub hdfs: Option<HdfsSettings>,
+}
+#[derive(serde::Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ScannerKind {
+    Noop,
+    Clamd,
+}
+#[derive(serde::Deserialize, Clone)]
+pub struct ScannerSettings {
+    /// What uploaded files are scanned with.
+    pub backend: ScannerKind,
+    /// The socket the `clamd` backend connects to.
+    pub socket: String,
+}
+#[derive(serde::Deserialize, Clone)]
+pub struct ImageSettings {
+    /// The widths images can be resized to when they are requested.
+    pub widths: Vec<u32>,
+    /// The qualities images can be encoded with when they are requested.
+    pub qualities: Vec<u8>,
+    /// The quality used when none is requested.
+    pub quality: u8,
+}
+#[derive(serde::Deserialize, Clone)]
+pub struct RecommendationSettings {
+    /// How often recommendations are recomputed, in seconds.
+    pub interval: u64,
+    /// How many books are recommended for each book and user.
+    pub limit: i32,
+    /// How long recommendations stay cached in Redis, in seconds.
+    pub ttl: u64,
+}
+#[derive(serde::Deserialize, Clone)]
+pub struct TrendingSettings {
+    /// How often trending books and reviews are recomputed, in seconds.
+    pub interval: u64,
+    /// How far back activity counts towards trending, in seconds.
+    pub window: i64,
+    /// How long it takes for activity to count half as much, in seconds.
+    pub half_life: i64,
+}
+#[derive(serde::Deserialize, Clone)]
+pub struct ImportSettings {
+    /// The directory catalog imports requested over HTTP read files from.
+    pub dir: String,
+    /// How many rows are imported in each transaction.
+    pub batch: usize,
+}
+#[derive(serde::Deserialize, Clone)]
 pub struct SecretSettings {
     /// The HMAC secret used for issuing tokens.
     pub sec: String,
@@ -56,6 +168,18 @@ pub struct Settings {
     pub frontend: FrontendSettings,
     /// Redis-related settings.
     pub redis: RedisSettings,
+    /// Storage-related settings.
+    pub storage: StorageSettings,
+    /// Scanner-related settings.
+    pub scanner: ScannerSettings,
+    /// Image-related settings.
+    pub images: ImageSettings,
+    /// Recommendation-related settings.
+    pub recommendations: RecommendationSettings,
+    /// Trending-related settings.
+    pub trending: TrendingSettings,
+    /// Import-related settings.
+    pub import: ImportSettings,
     /// Secret-related settings.
     pub secret: SecretSettings,
     /// Authencation-related setttings.
@@ -0,0 +1,170 @@
+use super::{ByteStream, Object, ObjectMetadata, StorageBackend, StorageError};
+use crate::hdfs::{AppHdfs, FileInfo};
+use axum::{async_trait, body::Bytes};
+use futures::{StreamExt, TryStreamExt};
+use libc::{O_RDONLY, O_WRONLY};
+use std::{
+    io::{ErrorKind, Seek, SeekFrom},
+    ops::Range,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+
+/// Stores objects as files under a directory in HDFS.
+pub struct HdfsStorage {
+    hdfs: Arc<AppHdfs>,
+    root: String,
+}
+
+impl HdfsStorage {
+    pub fn new(hdfs: AppHdfs, root: &str) -> Self {
+        Self {
+            hdfs: Arc::new(hdfs),
+            root: root.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    fn path(&self, key: &str) -> Result<String, StorageError> {
+        let is_valid = !key.is_empty()
+            && key
+                .split('/')
+                .all(|part| !part.is_empty() && part != "." && part != "..");
+        if !is_valid {
+            return Err(StorageError::InvalidKey(key.to_owned()));
+        }
+        Ok(format!("{}/{}", self.root, key))
+    }
+
+    /// Runs a blocking HDFS call on tokio's blocking thread pool.
+    async fn run<T, F>(&self, key: &str, f: F) -> Result<T, StorageError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Arc<AppHdfs>) -> std::io::Result<T> + Send + 'static,
+    {
+        let hdfs = Arc::clone(&self.hdfs);
+        tokio::task::spawn_blocking(move || f(&hdfs))
+            .await
+            .map_err(|err| StorageError::Backend(Box::new(err)))?
+            .map_err(|err| match err.kind() {
+                ErrorKind::NotFound => StorageError::NotFound(key.to_owned()),
+                _ => StorageError::Io(err),
+            })
+    }
+}
+
+fn metadata(info: &FileInfo) -> ObjectMetadata {
+    ObjectMetadata {
+        size: info.size.max(0) as u64,
+        last_modified: SystemTime::UNIX_EPOCH
+            .checked_add(Duration::from_secs(info.last_mod.max(0) as u64)),
+        etag: None,
+    }
+}
+
+/// Strips the scheme and authority from a name returned by `readdir`,
+/// e.g. `hdfs://localhost:9000/user/blisk/uploads/1-1.png`.
+fn strip_authority(name: &str) -> &str {
+    match name.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("", |i| &rest[i..]),
+        None => name,
+    }
+}
+
+#[async_trait]
+impl StorageBackend for HdfsStorage {
+    async fn put(&self, key: &str, body: Bytes) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        let file = self
+            .run(key, move |hdfs| {
+                if let Some((dir, _)) = path.rsplit_once('/') {
+                    hdfs.mkdir(dir)?;
+                }
+                hdfs.open(&path, O_WRONLY, None, None)
+            })
+            .await?;
+        let mut file = file.into_async();
+        file.write_all(&body).await?;
+        // Closing the file is what commits it, so its errors are not ignored.
+        file.close().await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Object, StorageError> {
+        let path = self.path(key)?;
+        let (info, file) = self
+            .run(key, move |hdfs| {
+                Ok((hdfs.read(&path)?, hdfs.open(&path, O_RDONLY, None, None)?))
+            })
+            .await?;
+        let body: ByteStream = ReaderStream::new(file.into_async())
+            .map_err(StorageError::Io)
+            .boxed();
+        Ok(Object {
+            metadata: metadata(&info),
+            body,
+        })
+    }
+
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<Object, StorageError> {
+        let path = self.path(key)?;
+        let (info, file) = self
+            .run(key, move |hdfs| {
+                let mut file = hdfs.open(&path, O_RDONLY, None, None)?;
+                file.seek(SeekFrom::Start(range.start))?;
+                Ok((hdfs.read(&path)?, file))
+            })
+            .await?;
+        let body: ByteStream = ReaderStream::new(file.into_async().take(range.end - range.start))
+            .map_err(StorageError::Io)
+            .boxed();
+        Ok(Object {
+            metadata: metadata(&info),
+            body,
+        })
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        match self.run(key, move |hdfs| hdfs.rm(&path)).await {
+            Err(StorageError::NotFound(_)) => Ok(()),
+            res => res,
+        }
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        let path = self.path(key)?;
+        let info = self.run(key, move |hdfs| hdfs.read(&path)).await?;
+        Ok(metadata(&info))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        let root = self.root.clone();
+        let mut keys = self
+            .run(prefix, move |hdfs| {
+                let root = match hdfs.read(&root) {
+                    Ok(info) => strip_authority(&info.name).to_owned(),
+                    Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+                    Err(err) => return Err(err),
+                };
+                let mut keys = Vec::new();
+                let mut directories = vec![root.clone()];
+                while let Some(directory) = directories.pop() {
+                    for entry in hdfs.readdir(&directory)? {
+                        let path = strip_authority(&entry.name).to_owned();
+                        if entry.is_dir() {
+                            directories.push(path);
+                        } else if let Some(key) = path.strip_prefix(&format!("{}/", root)) {
+                            keys.push(key.to_owned());
+                        }
+                    }
+                }
+                Ok(keys)
+            })
+            .await?;
+        keys.retain(|key| key.starts_with(prefix));
+        keys.sort();
+        Ok(keys)
+    }
+}
@@ -0,0 +1,137 @@
+use super::{ByteStream, Object, ObjectMetadata, StorageBackend, StorageError};
+use axum::{async_trait, body::Bytes};
+use futures::{StreamExt, TryStreamExt};
+use std::{
+    io::{ErrorKind, SeekFrom},
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// Stores objects as files under a directory on the local filesystem.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Maps `key` to a path under `root`, rejecting keys that
+    /// would escape it.
+    fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        let relative = Path::new(key);
+        let is_valid = relative.components().next().is_some()
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !is_valid {
+            return Err(StorageError::InvalidKey(key.to_owned()));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+fn not_found(key: &str) -> impl FnOnce(std::io::Error) -> StorageError + '_ {
+    move |err| match err.kind() {
+        ErrorKind::NotFound => StorageError::NotFound(key.to_owned()),
+        _ => StorageError::Io(err),
+    }
+}
+
+fn metadata(metadata: std::fs::Metadata) -> ObjectMetadata {
+    ObjectMetadata {
+        size: metadata.len(),
+        last_modified: metadata.modified().ok(),
+        etag: None,
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorage {
+    async fn put(&self, key: &str, body: Bytes) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // Write to a temporary file first so that readers never see
+        // a partially written object.
+        let temp_path = path.with_file_name(format!(".{}.tmp", uuid::Uuid::new_v4()));
+        tokio::fs::write(&temp_path, body).await?;
+        if let Err(err) = tokio::fs::rename(&temp_path, &path).await {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Object, StorageError> {
+        let file = tokio::fs::File::open(self.path(key)?)
+            .await
+            .map_err(not_found(key))?;
+        let metadata = metadata(file.metadata().await?);
+        let body: ByteStream = ReaderStream::new(file).map_err(StorageError::Io).boxed();
+        Ok(Object { metadata, body })
+    }
+
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<Object, StorageError> {
+        let mut file = tokio::fs::File::open(self.path(key)?)
+            .await
+            .map_err(not_found(key))?;
+        let metadata = metadata(file.metadata().await?);
+        file.seek(SeekFrom::Start(range.start)).await?;
+        let body: ByteStream = ReaderStream::new(file.take(range.end - range.start))
+            .map_err(StorageError::Io)
+            .boxed();
+        Ok(Object { metadata, body })
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        tokio::fs::metadata(self.path(key)?)
+            .await
+            .map(metadata)
+            .map_err(not_found(key))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        let mut keys = Vec::new();
+        let mut directories = vec![self.root.clone()];
+        while let Some(directory) = directories.pop() {
+            let mut entries = match tokio::fs::read_dir(&directory).await {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if entry.file_type().await?.is_dir() {
+                    directories.push(path);
+                    continue;
+                }
+                let Some(key) = path
+                    .strip_prefix(&self.root)
+                    .ok()
+                    .and_then(Path::to_str)
+                    .map(|key| key.replace(std::path::MAIN_SEPARATOR, "/"))
+                else {
+                    continue;
+                };
+                let is_temporary = entry.file_name().to_string_lossy().starts_with('.');
+                if !is_temporary && key.starts_with(prefix) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
@@ -0,0 +1,150 @@
+use super::{Object, ObjectMetadata, StorageBackend, StorageError};
+use axum::{async_trait, body::Bytes};
+use futures::StreamExt;
+use std::{collections::BTreeMap, sync::RwLock, time::SystemTime};
+
+/// Keeps objects in memory. Everything is lost when the application
+/// stops, so this is only meant for development and tests.
+#[derive(Default)]
+pub struct MemoryStorage {
+    objects: RwLock<BTreeMap<String, (Bytes, SystemTime)>>,
+}
+
+#[async_trait]
+impl StorageBackend for MemoryStorage {
+    async fn put(&self, key: &str, body: Bytes) -> Result<(), StorageError> {
+        self.objects
+            .write()
+            .unwrap()
+            .insert(key.to_owned(), (body, SystemTime::now()));
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Object, StorageError> {
+        let (body, last_modified) = self
+            .objects
+            .read()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| StorageError::NotFound(key.to_owned()))?;
+        Ok(Object {
+            metadata: ObjectMetadata {
+                size: body.len() as u64,
+                last_modified: Some(last_modified),
+                etag: None,
+            },
+            body: futures::stream::once(async { Ok(body) }).boxed(),
+        })
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.objects.write().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        self.objects
+            .read()
+            .unwrap()
+            .get(key)
+            .map(|(body, last_modified)| ObjectMetadata {
+                size: body.len() as u64,
+                last_modified: Some(*last_modified),
+                etag: None,
+            })
+            .ok_or_else(|| StorageError::NotFound(key.to_owned()))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        Ok(self
+            .objects
+            .read()
+            .unwrap()
+            .range(prefix.to_owned()..)
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryStorage;
+    use crate::storage::{StorageBackend, StorageError};
+    use axum::body::Bytes;
+    use futures::TryStreamExt;
+
+    async fn read(storage: &dyn StorageBackend, key: &str) -> Vec<u8> {
+        let object = storage.get(key).await.unwrap();
+        let chunks = object.body.try_collect::<Vec<_>>().await.unwrap();
+        chunks.concat()
+    }
+
+    #[tokio::test]
+    async fn writes_and_reads_objects() {
+        let storage: &dyn StorageBackend = &MemoryStorage::default();
+        storage
+            .put("a/1.png", Bytes::from_static(b"first"))
+            .await
+            .unwrap();
+        storage
+            .put("a/1.png", Bytes::from_static(b"second"))
+            .await
+            .unwrap();
+        assert_eq!(read(storage, "a/1.png").await, b"second");
+        assert_eq!(storage.head("a/1.png").await.unwrap().size, 6);
+        assert!(storage.head("a/1.png").await.unwrap().etag().is_some());
+    }
+
+    #[tokio::test]
+    async fn reads_ranges() {
+        let storage: &dyn StorageBackend = &MemoryStorage::default();
+        storage
+            .put("key", Bytes::from_static(b"0123456789"))
+            .await
+            .unwrap();
+        let object = storage.get_range("key", 2..5).await.unwrap();
+        assert_eq!(object.metadata.size, 10);
+        let chunks = object.body.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(chunks.concat(), b"234");
+    }
+
+    #[tokio::test]
+    async fn lists_by_prefix() {
+        let storage: &dyn StorageBackend = &MemoryStorage::default();
+        for key in [
+            "cache/a/full.png",
+            "cache/a/64.png",
+            "cache/b/full.png",
+            "a",
+        ] {
+            storage.put(key, Bytes::new()).await.unwrap();
+        }
+        assert_eq!(
+            storage.list("cache/a/").await.unwrap(),
+            ["cache/a/64.png", "cache/a/full.png"]
+        );
+    }
+
+    #[tokio::test]
+    async fn deletes_objects() {
+        let storage: &dyn StorageBackend = &MemoryStorage::default();
+        storage
+            .put("key", Bytes::from_static(b"body"))
+            .await
+            .unwrap();
+        storage.delete("key").await.unwrap();
+        // Deleting a missing object is not an error.
+        storage.delete("key").await.unwrap();
+        assert!(matches!(
+            storage.get("key").await,
+            Err(StorageError::NotFound(key)) if key == "key"
+        ));
+        assert!(matches!(
+            storage.head("key").await,
+            Err(StorageError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+#[cfg(feature = "hdfs")]
+pub mod hdfs;
+pub mod local;
+pub mod memory;
+pub mod s3;
+
+use crate::settings::{StorageKind, StorageSettings};
+use axum::{async_trait, body::Bytes};
+use futures::{stream::BoxStream, StreamExt};
+use std::{
+    collections::BTreeMap,
+    ops::Range,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("object {0} cannot be found")]
+    NotFound(String),
+    #[error("received an invalid key: {0}")]
+    InvalidKey(String),
+    #[error("received an IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("the storage backend does not support {0}")]
+    Unsupported(&'static str),
+    #[error("error while talking to the storage backend: {0}")]
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// The body of an object, streamed in chunks.
+pub type ByteStream = BoxStream<'static, Result<Bytes, StorageError>>;
+
+#[derive(Clone, Debug)]
+pub struct ObjectMetadata {
+    /// The size of the object in bytes.
+    pub size: u64,
+    /// When the object was last written to, if the backend knows.
+    pub last_modified: Option<SystemTime>,
+    /// The entity tag the backend assigned to the object, if any.
+    pub etag: Option<String>,
+}
+
+impl ObjectMetadata {
+    /// Returns a quoted entity tag for the object. If the backend has not
+    /// assigned one, it is derived from the object's size and modification
+    /// time, which is enough since objects are never modified in place.
+    pub fn etag(&self) -> Option<String> {
+        self.etag.clone().or_else(|| {
+            let last_modified = self.last_modified?;
+            let nanos = last_modified
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos();
+            Some(format!("\"{:x}-{:x}\"", self.size, nanos))
+        })
+    }
+}
+
+pub struct Object {
+    pub metadata: ObjectMetadata,
+    pub body: ByteStream,
+}
+
+/// A request clients can send to access the storage backend directly.
+#[derive(Debug, serde::Serialize)]
+pub struct PresignedRequest {
+    pub method: String,
+    pub url: String,
+    /// Headers that must be sent exactly as given.
+    pub headers: BTreeMap<String, String>,
+}
+
+/// Where uploaded files are stored. Keys are `/`-separated paths
+/// relative to the root of the backend.
+///
+/// Objects are streamed when they are read, but written from a single
+/// buffer: every upload is decoded and re-encoded in memory before it is
+/// stored, and is at most `storage.limits.size` bytes, so there is no
+/// stream left to pass on by then. Large files bypass the application
+/// through presigned uploads instead.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Writes `body` to `key`, replacing the object if it already exists.
+    async fn put(&self, key: &str, body: Bytes) -> Result<(), StorageError>;
+    /// Streams the object stored at `key`.
+    async fn get(&self, key: &str) -> Result<Object, StorageError>;
+    /// Streams the bytes of the object stored at `key` that lie within `range`.
+    /// The returned metadata still describes the whole object.
+    ///
+    /// The default implementation reads the object from the start and
+    /// discards whatever lies outside of `range`.
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<Object, StorageError> {
+        let object = self.get(key).await?;
+        let body = futures::stream::unfold(
+            (object.body, 0),
+            move |(mut body, mut offset)| async move {
+                while offset < range.end {
+                    let chunk = match body.next().await? {
+                        Ok(chunk) => chunk,
+                        Err(err) => return Some((Err(err), (body, range.end))),
+                    };
+                    let start = offset;
+                    offset += chunk.len() as u64;
+                    let from = range.start.saturating_sub(start).min(chunk.len() as u64);
+                    let to = range.end.saturating_sub(start).min(chunk.len() as u64);
+                    if from < to {
+                        let chunk = chunk.slice(from as usize..to as usize);
+                        return Some((Ok(chunk), (body, offset)));
+                    }
+                }
+                None
+            },
+        )
+        .boxed();
+        Ok(Object {
+            metadata: object.metadata,
+            body,
+        })
+    }
+    /// Deletes the object stored at `key`. Deleting an object that
+    /// does not exist is not an error.
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+    /// Reads the metadata of the object stored at `key`.
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError>;
+    /// Lists the keys that start with `prefix`.
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError>;
+    /// Creates a request that uploads an object of exactly `size` bytes
+    /// and of type `content_type` to `key`, valid for `expires_in`.
+    async fn presign_put(
+        &self,
+        _key: &str,
+        _content_type: &str,
+        _size: u64,
+        _expires_in: Duration,
+    ) -> Result<PresignedRequest, StorageError> {
+        Err(StorageError::Unsupported("presigned uploads"))
+    }
+}
+
+/// Creates the storage backend selected in `settings`.
+pub async fn from_settings(settings: &StorageSettings) -> Arc<dyn StorageBackend> {
+    match settings.backend {
+        StorageKind::S3 => {
+            let config = aws_config::load_from_env().await;
+            Arc::new(s3::S3Storage::new(
+                aws_sdk_s3::Client::new(&config),
+                settings.bucket.clone(),
+            ))
+        }
+        StorageKind::Local => Arc::new(local::LocalStorage::new(&settings.root)),
+        StorageKind::Memory => Arc::new(memory::MemoryStorage::default()),
+        #[cfg(feature = "hdfs")]
+        StorageKind::Hdfs => {
+            let hdfs_settings = settings
+                .hdfs
+                .as_ref()
+                .expect("`storage.hdfs` must be set to use the HDFS backend");
+            let hdfs = crate::hdfs::AppHdfs::new(&hdfs_settings.name_node, &hdfs_settings.user)
+                .expect("Failed to initiate HDFS");
+            Arc::new(hdfs::HdfsStorage::new(hdfs, &settings.root))
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use super::{ByteStream, Object, ObjectMetadata, PresignedRequest, StorageBackend, StorageError};
+use aws_sdk_s3::{presigning::PresigningConfig, primitives::DateTime};
+use axum::{async_trait, body::Bytes};
+use futures::StreamExt;
+use std::{
+    ops::Range,
+    time::{Duration, SystemTime},
+};
+
+/// Stores objects in an S3 bucket.
+pub struct S3Storage {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+}
+
+impl S3Storage {
+    pub fn new(client: aws_sdk_s3::Client, bucket: String) -> Self {
+        Self { client, bucket }
+    }
+}
+
+fn backend(err: impl std::error::Error + Send + Sync + 'static) -> StorageError {
+    StorageError::Backend(Box::new(err))
+}
+
+fn last_modified(time: Option<&DateTime>) -> Option<SystemTime> {
+    time.and_then(|time| SystemTime::try_from(*time).ok())
+}
+
+fn body(body: aws_sdk_s3::primitives::ByteStream) -> ByteStream {
+    futures::stream::unfold(body, |mut body| async move {
+        body.next()
+            .await
+            .map(|chunk| (chunk.map_err(backend), body))
+    })
+    .boxed()
+}
+
+#[async_trait]
+impl StorageBackend for S3Storage {
+    async fn put(&self, key: &str, body: Bytes) -> Result<(), StorageError> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(body.into())
+            .send()
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Object, StorageError> {
+        let res = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| match err.as_service_error() {
+                Some(service_err) if service_err.is_no_such_key() => {
+                    StorageError::NotFound(key.to_owned())
+                }
+                _ => backend(err),
+            })?;
+        let metadata = ObjectMetadata {
+            size: res.content_length().unwrap_or_default().max(0) as u64,
+            last_modified: last_modified(res.last_modified()),
+            etag: res.e_tag().map(str::to_owned),
+        };
+        Ok(Object {
+            metadata,
+            body: body(res.body),
+        })
+    }
+
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<Object, StorageError> {
+        let res = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .range(format!("bytes={}-{}", range.start, range.end - 1))
+            .send()
+            .await
+            .map_err(|err| match err.as_service_error() {
+                Some(service_err) if service_err.is_no_such_key() => {
+                    StorageError::NotFound(key.to_owned())
+                }
+                _ => backend(err),
+            })?;
+        // `Content-Range` looks like `bytes 0-99/1234`.
+        let size = res
+            .content_range()
+            .and_then(|content_range| content_range.rsplit_once('/'))
+            .and_then(|(_, size)| size.parse().ok())
+            .unwrap_or_default();
+        let metadata = ObjectMetadata {
+            size,
+            last_modified: last_modified(res.last_modified()),
+            etag: res.e_tag().map(str::to_owned),
+        };
+        Ok(Object {
+            metadata,
+            body: body(res.body),
+        })
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        let res = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| match err.as_service_error() {
+                Some(service_err) if service_err.is_not_found() => {
+                    StorageError::NotFound(key.to_owned())
+                }
+                _ => backend(err),
+            })?;
+        Ok(ObjectMetadata {
+            size: res.content_length().unwrap_or_default().max(0) as u64,
+            last_modified: last_modified(res.last_modified()),
+            etag: res.e_tag().map(str::to_owned),
+        })
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        let mut keys = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let res = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(prefix)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await
+                .map_err(backend)?;
+            keys.extend(
+                res.contents()
+                    .iter()
+                    .filter_map(|object| object.key().map(str::to_owned)),
+            );
+            continuation_token = res.next_continuation_token().map(str::to_owned);
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+        Ok(keys)
+    }
+
+    async fn presign_put(
+        &self,
+        key: &str,
+        content_type: &str,
+        size: u64,
+        expires_in: Duration,
+    ) -> Result<PresignedRequest, StorageError> {
+        let config = PresigningConfig::expires_in(expires_in).map_err(backend)?;
+        let req = self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .content_length(size as i64)
+            .presigned(config)
+            .await
+            .map_err(backend)?;
+        Ok(PresignedRequest {
+            method: req.method().to_owned(),
+            url: req.uri().to_owned(),
+            headers: req
+                .headers()
+                .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                .collect(),
+        })
+    }
+}
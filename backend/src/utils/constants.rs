@@ -8,3 +8,20 @@ pub static TEMPLATES: LazyLock<minijinja::Environment<'static>> = LazyLock::new(
 
 pub static SLUG_REGEX: LazyLock<regex::Regex> =
     LazyLock::new(|| regex::Regex::new("^[a-z0-9](-?[a-z0-9])*$").unwrap());
+
+/// The maximum number of characters in a post's content.
+pub const MAX_POST_CONTENT_LENGTH: u64 = 20_000;
+
+/// The maximum number of characters in a comment's content.
+pub const MAX_COMMENT_CONTENT_LENGTH: u64 = 5_000;
+
+/// Matches `@username`, unless it is part of a word or an email address.
+pub static MENTION_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?:^|[^\w@])@(\w(?:[\w.-]*\w)?)").unwrap());
+
+/// Matches `#tag`, unless it is part of a word or an HTML entity.
+pub static TAG_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?:^|[^\w#&])#(\w[\w-]*)").unwrap());
+
+/// The maximum number of characters in a tag.
+pub const MAX_TAG_LENGTH: usize = 64;
@@ -1,9 +1,11 @@
 use super::{response::ErrorResponse, structs::AppJson, uploads::UploadsError};
 use crate::{
+    jobs::import::ImportError,
     routes::{
         auth::AuthError, books::BooksError, comments::CommentsError, posts::PostsError,
         users::UserError,
     },
+    storage::StorageError,
     utils::response::ValidationErrorResponse,
 };
 use axum::{
@@ -32,6 +34,8 @@ pub enum AppError {
     BooksError(#[from] BooksError),
     #[error("error while uploading a file: {0}")]
     UploadsError(#[from] UploadsError),
+    #[error("error while importing books: {0}")]
+    ImportError(#[from] ImportError),
     #[error("error was not expected {0}")]
     Unexpected(&'static str),
     #[error("error while procesing form: {0}")]
@@ -89,6 +93,18 @@ impl IntoResponse for AppError {
                         StatusCode::UNAUTHORIZED,
                         "Invalid credentials provided. The requested user may or may not already exist, or may be incorrect.".to_owned(),
                     ),
+                    AuthError::Banned => (
+                        StatusCode::FORBIDDEN,
+                        "This account has been banned.".to_owned(),
+                    ),
+                    AuthError::Suspended(until) => (
+                        StatusCode::FORBIDDEN,
+                        format!("This account has been suspended until {}.", until.format("%a, %b %d %Y %X UTC")),
+                    ),
+                    AuthError::Forbidden => (
+                        StatusCode::FORBIDDEN,
+                        "You are not allowed to perform this action.".to_owned(),
+                    ),
                     AuthError::Unexpected => (
                         StatusCode::INTERNAL_SERVER_ERROR,
                         "Internal Server Error".to_owned(),
@@ -101,6 +117,18 @@ impl IntoResponse for AppError {
                         StatusCode::NOT_FOUND,
                         format!("User {} may have been banned, or the username is incorrect.", username)
                     ),
+                    UserError::Blocked => (
+                        StatusCode::FORBIDDEN,
+                        "You cannot interact with this user's content.".to_owned()
+                    ),
+                    UserError::CannotRestrictSelf => (
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        "You cannot block or mute yourself.".to_owned()
+                    ),
+                    UserError::AlreadyBlocked => (
+                        StatusCode::CONFLICT,
+                        "You cannot mute an user you blocked. Unblock them first.".to_owned()
+                    ),
                     UserError::Unexpected => (
                         StatusCode::INTERNAL_SERVER_ERROR,
                         "Internal Server Error".to_owned()
@@ -159,6 +187,26 @@ impl IntoResponse for AppError {
                         StatusCode::CONFLICT,
                         format!("You are already reading book {}.", name)
                     ),
+                    BooksError::IsbnAlreadyExists(isbn) => (
+                        StatusCode::CONFLICT,
+                        format!("An edition with ISBN {isbn} already exists!")
+                    ),
+                    BooksError::EditionNotFound(_) => (
+                        StatusCode::NOT_FOUND,
+                        "Edition not found.".to_owned()
+                    ),
+                    BooksError::SeriesAlreadyExists(name) => (
+                        StatusCode::CONFLICT,
+                        format!("Series {name} already exists!")
+                    ),
+                    BooksError::SeriesNotFound(_) => (
+                        StatusCode::NOT_FOUND,
+                        "Series not found.".to_owned()
+                    ),
+                    BooksError::SeriesPositionTaken(position) => (
+                        StatusCode::CONFLICT,
+                        format!("Another book is already at position {position} of the series.")
+                    ),
                     BooksError::Unexpected => (
                         StatusCode::INTERNAL_SERVER_ERROR,
                         "Internal Server Error".to_owned()
@@ -175,12 +223,98 @@ impl IntoResponse for AppError {
                         StatusCode::INTERNAL_SERVER_ERROR,
                         "Internal Server Error".to_owned()
                     ),
+                    UploadsError::StorageError(StorageError::NotFound(_)) => (
+                        StatusCode::NOT_FOUND,
+                        "File not found.".to_owned()
+                    ),
+                    UploadsError::UnsupportedFormat => (
+                        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                        "Only PNG, JPEG, GIF and WebP images are supported.".to_owned()
+                    ),
+                    UploadsError::InvalidImage(_) => (
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        "The image could not be processed.".to_owned()
+                    ),
+                    UploadsError::TooLarge(limit) => (
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        format!("Files must not be larger than {limit} bytes!")
+                    ),
+                    UploadsError::TypeNotAllowed(mime_type) => (
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        format!("Files of type {mime_type} are not allowed!")
+                    ),
+                    UploadsError::QuotaExceeded { quota, .. } => (
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        format!("This would exceed your storage quota of {quota} bytes!")
+                    ),
+                    UploadsError::MissingFile => (
+                        StatusCode::BAD_REQUEST,
+                        "A file must be uploaded!".to_owned()
+                    ),
+                    UploadsError::FileNotFound(_) => (
+                        StatusCode::NOT_FOUND,
+                        "File not found.".to_owned()
+                    ),
+                    UploadsError::FileNotUploaded => (
+                        StatusCode::CONFLICT,
+                        "File has not been uploaded yet.".to_owned()
+                    ),
+                    UploadsError::FileInUse(_) => (
+                        StatusCode::CONFLICT,
+                        "File is still used by a book or a profile.".to_owned()
+                    ),
+                    UploadsError::FileIsPrivate(_) => (
+                        StatusCode::CONFLICT,
+                        "Private files cannot be used by books or profiles.".to_owned()
+                    ),
+                    UploadsError::FileQuarantined(_) => (
+                        StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+                        "File was flagged as malicious and is unavailable.".to_owned()
+                    ),
+                    UploadsError::ScannerError(_) => (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        "Files cannot be scanned right now, please try again later.".to_owned()
+                    ),
+                    UploadsError::StorageError(StorageError::Unsupported(_)) => (
+                        StatusCode::NOT_IMPLEMENTED,
+                        "This is not supported by the storage backend.".to_owned()
+                    ),
                     _ => (
                         StatusCode::INTERNAL_SERVER_ERROR,
                         "Internal Server Error".to_owned()
                     )
                 }
             },
+            AppError::ImportError(error) => {
+                match error {
+                    ImportError::FileNotFound(file) => (
+                        StatusCode::NOT_FOUND,
+                        format!("File {file} cannot be found.")
+                    ),
+                    ImportError::UnsupportedFormat(format) => (
+                        StatusCode::BAD_REQUEST,
+                        format!("Format {format} is not supported!")
+                    ),
+                    ImportError::Malformed(reason) => (
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        format!("File is malformed: {reason}")
+                    ),
+                    ImportError::WorkNotFound(work) => (
+                        StatusCode::NOT_FOUND,
+                        format!("Work {work} must be imported before its editions!")
+                    ),
+                    ImportError::JobNotFound(id) => (
+                        StatusCode::NOT_FOUND,
+                        format!("Import {id} cannot be found.")
+                    ),
+                    ImportError::Panicked
+                    | ImportError::Interrupted
+                    | ImportError::IoError(_) => (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Internal Server Error".to_owned()
+                    ),
+                }
+            },
             AppError::Unexpected(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Internal Server Error".to_owned(),
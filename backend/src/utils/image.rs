@@ -1,43 +1,110 @@
+use crate::storage::{ByteStream, ObjectMetadata};
 use axum::{
     body::Body,
-    http::{HeaderValue, StatusCode},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
+use axum_extra::headers::{
+    AcceptRanges, ContentLength, ContentRange, ETag, HeaderMapExt, LastModified,
+};
+use std::ops::Range;
 
 pub enum Image {
     Filename(String),
-    File(String, Vec<u8>),
+    /// A stored file, or the part of it within `range`.
+    File {
+        name: String,
+        metadata: ObjectMetadata,
+        is_private: bool,
+        body: ByteStream,
+        range: Option<Range<u64>>,
+    },
+    /// The client's cached copy of the file is still fresh.
+    NotModified {
+        metadata: ObjectMetadata,
+        is_private: bool,
+    },
+    /// The requested range lies outside of a file of the given size.
+    RangeNotSatisfiable(u64),
+}
+
+/// Inserts the headers that let clients cache the file and revalidate it later.
+/// Private files must not be kept by shared caches.
+fn insert_validators(headers: &mut HeaderMap, metadata: &ObjectMetadata, is_private: bool) {
+    if let Some(etag) = metadata.etag().and_then(|etag| etag.parse::<ETag>().ok()) {
+        headers.typed_insert(etag);
+    }
+    if let Some(last_modified) = metadata.last_modified {
+        headers.typed_insert(LastModified::from(last_modified));
+    }
+    let cache_control = if is_private {
+        "private, no-cache"
+    } else {
+        "public, max-age=31536000, immutable"
+    };
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(cache_control),
+    );
 }
 
 impl IntoResponse for Image {
     fn into_response(self) -> Response {
         match self {
             Self::Filename(name) => (StatusCode::OK, name).into_response(),
-            Self::File(filename, data) => {
-                let filename_header_value = format!("attachment; filename=\"{filename}\"");
+            Self::File {
+                name,
+                metadata,
+                is_private,
+                body,
+                range,
+            } => {
+                let filename_header_value = format!("attachment; filename=\"{name}\"");
 
-                let mime = mime_guess::from_path(filename)
+                let mime = mime_guess::from_path(name)
                     .first_raw()
                     .map(HeaderValue::from_static)
                     .unwrap_or(HeaderValue::from_static("application/octet-stream"));
 
-                Response::builder()
+                let mut res = Response::builder()
                     .header("Content-Disposition", filename_header_value)
                     .header("Content-Type", mime)
-                    .header("Cache-Control", "public, max-age=31536000, immutable")
-                    .body(Body::from(data))
-                    .unwrap()
+                    .body(Body::from_stream(body))
+                    .unwrap();
+
+                let headers = res.headers_mut();
+                insert_validators(headers, &metadata, is_private);
+                headers.typed_insert(AcceptRanges::bytes());
+                match range {
+                    Some(range) => {
+                        headers.typed_insert(ContentLength(range.end - range.start));
+                        if let Ok(content_range) = ContentRange::bytes(range, metadata.size) {
+                            headers.typed_insert(content_range);
+                        }
+                        *res.status_mut() = StatusCode::PARTIAL_CONTENT;
+                    }
+                    None => headers.typed_insert(ContentLength(metadata.size)),
+                }
+                res
+            }
+            Self::NotModified {
+                metadata,
+                is_private,
+            } => {
+                let mut res = StatusCode::NOT_MODIFIED.into_response();
+                insert_validators(res.headers_mut(), &metadata, is_private);
+                res
+            }
+            Self::RangeNotSatisfiable(size) => {
+                let mut res = StatusCode::RANGE_NOT_SATISFIABLE.into_response();
+                res.headers_mut()
+                    .typed_insert(ContentRange::unsatisfied_bytes(size));
+                res
             }
         }
     }
 }
 
-impl Into<Image> for (String, Vec<u8>) {
-    fn into(self) -> Image {
-        Image::File(self.0, self.1)
-    }
-}
-
 impl Into<Image> for String {
     fn into(self) -> Image {
         Image::Filename(self)
@@ -0,0 +1,140 @@
+/// Returns the ISBN-13 of `isbn`, which can be either an ISBN-10 or an
+/// ISBN-13, with or without hyphens and spaces. Returns `None` when it is
+/// malformed or its check digit is wrong.
+pub fn normalize(isbn: &str) -> Option<String> {
+    let isbn = isbn
+        .chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .collect::<String>()
+        .to_ascii_uppercase();
+    // Lengths and splits below are in bytes, so only ASCII makes sense.
+    if !isbn.is_ascii() {
+        return None;
+    }
+    match isbn.len() {
+        10 => {
+            let (body, check) = isbn.split_at(9);
+            let digits = digits(body)?;
+            let expected = isbn10_check_digit(&digits);
+            if check != expected.to_string() {
+                return None;
+            }
+            let mut digits13 = vec![9, 7, 8];
+            digits13.extend(digits);
+            let check = isbn13_check_digit(&digits13);
+            Some(format!("978{body}{check}"))
+        }
+        13 => {
+            let digits = digits(&isbn)?;
+            let (body, check) = digits.split_at(12);
+            if !(isbn.starts_with("978") || isbn.starts_with("979"))
+                || isbn13_check_digit(body) != check[0]
+            {
+                return None;
+            }
+            Some(isbn)
+        }
+        _ => None,
+    }
+}
+
+fn digits(value: &str) -> Option<Vec<u32>> {
+    value.chars().map(|c| c.to_digit(10)).collect()
+}
+
+/// The check digit of the first 9 digits of an ISBN-10, where 10 is `X`.
+fn isbn10_check_digit(digits: &[u32]) -> char {
+    let sum = digits
+        .iter()
+        .zip((2..=10).rev())
+        .map(|(digit, weight)| digit * weight)
+        .sum::<u32>();
+    match (11 - sum % 11) % 11 {
+        10 => 'X',
+        check => char::from_digit(check, 10).unwrap_or('0'),
+    }
+}
+
+/// The check digit of the first 12 digits of an ISBN-13.
+fn isbn13_check_digit(digits: &[u32]) -> u32 {
+    let sum = digits
+        .iter()
+        .enumerate()
+        .map(|(i, digit)| if i % 2 == 0 { *digit } else { digit * 3 })
+        .sum::<u32>();
+    (10 - sum % 10) % 10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize;
+
+    #[test]
+    fn keeps_valid_isbn13() {
+        assert_eq!(normalize("9780306406157").as_deref(), Some("9780306406157"));
+        assert_eq!(normalize("9791090636071").as_deref(), Some("9791090636071"));
+    }
+
+    #[test]
+    fn converts_valid_isbn10() {
+        assert_eq!(normalize("0306406152").as_deref(), Some("9780306406157"));
+    }
+
+    #[test]
+    fn rejects_bad_checksums() {
+        assert_eq!(normalize("0306406153"), None);
+        assert_eq!(normalize("9780306406158"), None);
+        assert_eq!(normalize("9791090636072"), None);
+    }
+
+    #[test]
+    fn rejects_malformed_isbns() {
+        assert_eq!(normalize(""), None);
+        assert_eq!(normalize("030640615"), None);
+        assert_eq!(normalize("97803064061570"), None);
+        assert_eq!(normalize("03064O6152"), None);
+        // Only ISBN-13s starting with 978 or 979 exist.
+        assert_eq!(normalize("9770306406158"), None);
+    }
+
+    #[test]
+    fn rejects_non_ascii_isbns() {
+        assert_eq!(normalize("12345678é"), None);
+        assert_eq!(normalize("030640615é"), None);
+        assert_eq!(normalize("978030640615é"), None);
+        assert_eq!(normalize("０３０６４０６１５２"), None);
+    }
+
+    #[test]
+    fn ignores_hyphens_and_spaces() {
+        assert_eq!(
+            normalize("978-0-306-40615-7").as_deref(),
+            Some("9780306406157")
+        );
+        assert_eq!(normalize("0-306-40615-2").as_deref(), Some("9780306406157"));
+        assert_eq!(
+            normalize("978 0 306 40615 7").as_deref(),
+            Some("9780306406157")
+        );
+    }
+
+    #[test]
+    fn accepts_x_check_digit() {
+        assert_eq!(normalize("080442957X").as_deref(), Some("9780804429573"));
+        assert_eq!(normalize("0-8044-2957-x").as_deref(), Some("9780804429573"));
+        assert_eq!(normalize("0804429570"), None);
+        // `X` is only a check digit of ISBN-10s.
+        assert_eq!(normalize("978080442957X"), None);
+    }
+
+    #[test]
+    fn does_not_convert_979_prefix() {
+        // ISBN-13s starting with 979 have no ISBN-10, so they are kept as is
+        // and their last 10 digits are not mistaken for one.
+        assert_eq!(
+            normalize("979-10-90636-07-1").as_deref(),
+            Some("9791090636071")
+        );
+        assert_ne!(normalize("1090636071").as_deref(), Some("9791090636071"));
+    }
+}
@@ -0,0 +1,222 @@
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
+
+/// Spoilers are wrapped in double pipes, e.g. "The butler ||did it||."
+const SPOILER_DELIMITER: &str = "||";
+
+const SPOILER_OPEN: &str = r#"<span class="spoiler">"#;
+
+const SPOILER_CLOSE: &str = "</span>";
+
+/// Everything that is not explicitly allowed here is stripped from
+/// the rendered HTML. Images are not allowed, since remote ones would let
+/// authors track who reads them, see `images_as_links`.
+static SANITIZER: LazyLock<ammonia::Builder<'static>> = LazyLock::new(|| {
+    let mut builder = ammonia::Builder::empty();
+    builder
+        .add_tags([
+            "a",
+            "blockquote",
+            "br",
+            "code",
+            "del",
+            "em",
+            "h1",
+            "h2",
+            "h3",
+            "h4",
+            "h5",
+            "h6",
+            "hr",
+            "li",
+            "ol",
+            "p",
+            "pre",
+            "span",
+            "strong",
+            "table",
+            "tbody",
+            "td",
+            "th",
+            "thead",
+            "tr",
+            "ul",
+        ])
+        .tag_attributes(HashMap::from([
+            ("a", HashSet::from(["href", "title"])),
+            ("ol", HashSet::from(["start"])),
+            ("td", HashSet::from(["align"])),
+            ("th", HashSet::from(["align"])),
+        ]))
+        .allowed_classes(HashMap::from([("span", HashSet::from(["spoiler"]))]))
+        .clean_content_tags(HashSet::from(["script", "style"]))
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .url_relative(ammonia::UrlRelative::Deny)
+        .link_rel(Some("nofollow noopener noreferrer"));
+    builder
+});
+
+/// Whether `tag` can only contain inline content, meaning
+/// that a spoiler may span across it.
+fn is_inline(tag: &TagEnd) -> bool {
+    matches!(
+        tag,
+        TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough | TagEnd::Link | TagEnd::Image
+    )
+}
+
+enum Piece<'a> {
+    Event(Event<'a>),
+    Delimiter,
+}
+
+/// Pairs spoiler delimiters in a run of inline events. An unclosed
+/// delimiter is kept as text, the same way `parse_spoilers` does.
+fn flush_spoilers<'a>(run: &mut Vec<Piece<'a>>, events: &mut Vec<Event<'a>>) {
+    let delimiters = run
+        .iter()
+        .filter(|piece| matches!(piece, Piece::Delimiter))
+        .count();
+    let mut seen = 0;
+    for piece in run.drain(..) {
+        match piece {
+            Piece::Event(event) => events.push(event),
+            Piece::Delimiter => {
+                seen += 1;
+                if seen == delimiters && delimiters % 2 == 1 {
+                    events.push(Event::Text(CowStr::Borrowed(SPOILER_DELIMITER)));
+                } else if seen % 2 == 1 {
+                    events.push(Event::InlineHtml(CowStr::Borrowed(SPOILER_OPEN)));
+                } else {
+                    events.push(Event::InlineHtml(CowStr::Borrowed(SPOILER_CLOSE)));
+                }
+            }
+        }
+    }
+}
+
+/// Turns images into links to them, with their alternative text as the
+/// text of the link.
+fn images_as_links(event: Event<'_>) -> Event<'_> {
+    match event {
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }),
+        Event::End(TagEnd::Image) => Event::End(TagEnd::Link),
+        event => event,
+    }
+}
+
+/// Turns `||spoiler||` into `<span class="spoiler">spoiler</span>`.
+fn with_spoilers<'a>(parser: impl Iterator<Item = Event<'a>>) -> Vec<Event<'a>> {
+    let mut events = Vec::new();
+    let mut run = Vec::new();
+    let mut in_code_block = false;
+    for event in parser {
+        match event {
+            Event::Text(text) if !in_code_block && text.contains(SPOILER_DELIMITER) => {
+                for (i, part) in text.split(SPOILER_DELIMITER).enumerate() {
+                    if i != 0 {
+                        run.push(Piece::Delimiter);
+                    }
+                    if !part.is_empty() {
+                        run.push(Piece::Event(Event::Text(CowStr::from(part.to_owned()))));
+                    }
+                }
+            }
+            Event::Start(ref tag) if !is_inline(&tag.to_end()) => {
+                in_code_block = matches!(tag, Tag::CodeBlock(_));
+                flush_spoilers(&mut run, &mut events);
+                events.push(event);
+            }
+            Event::End(ref tag) if !is_inline(tag) => {
+                in_code_block = false;
+                flush_spoilers(&mut run, &mut events);
+                events.push(event);
+            }
+            event => run.push(Piece::Event(event)),
+        }
+    }
+    flush_spoilers(&mut run, &mut events);
+    events
+}
+
+/// Renders user-provided Markdown into sanitized HTML.
+pub fn render(content: &str) -> String {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let parser = Parser::new_ext(content, options).map(images_as_links);
+    let mut unsafe_html = String::with_capacity(content.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, with_spoilers(parser).into_iter());
+    SANITIZER.clean(&unsafe_html).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+
+    #[test]
+    fn strips_scripts() {
+        let html = render("Hello <script>alert(1)</script>world");
+        assert!(!html.contains("script"));
+        assert!(!html.contains("alert"));
+    }
+
+    #[test]
+    fn strips_javascript_links() {
+        let html = render("[click](javascript:alert(1)) <a href=\"javascript:alert(1)\">here</a>");
+        assert!(!html.contains("javascript"));
+    }
+
+    #[test]
+    fn strips_event_handlers() {
+        let html = render(
+            r#"<p onclick="alert(1)">hi</p> <a href="https://a.b" onmouseover="alert(1)">x</a>"#,
+        );
+        assert!(!html.contains("onclick"));
+        assert!(!html.contains("onmouseover"));
+        assert!(!html.contains("alert"));
+    }
+
+    #[test]
+    fn keeps_spoilers() {
+        assert_eq!(
+            render("The butler ||did it||."),
+            "<p>The butler <span class=\"spoiler\">did it</span>.</p>\n"
+        );
+    }
+
+    #[test]
+    fn strips_other_classes() {
+        let html = render(r#"<span class="spoiler evil">x</span>"#);
+        assert!(html.contains(r#"<span class="spoiler">x</span>"#));
+        assert!(!html.contains("evil"));
+    }
+
+    #[test]
+    fn sets_link_rel() {
+        assert_eq!(
+            render("[blisk](https://blisk.example)"),
+            "<p><a href=\"https://blisk.example\" rel=\"nofollow noopener noreferrer\">blisk</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn turns_images_into_links() {
+        let html = render("![a cover](https://tracker.example/pixel.png)");
+        assert!(!html.contains("<img"));
+        assert!(html.contains(r#"<a href="https://tracker.example/pixel.png""#));
+        assert!(html.contains("a cover"));
+        assert!(!render(r#"<img src="https://tracker.example/pixel.png">"#).contains("<img"));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::utils::constants::{MAX_TAG_LENGTH, MENTION_REGEX, TAG_REGEX};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use sqlx::Postgres;
+
+/// The `@username`s and `#tag`s found in a post or a comment.
+#[derive(Debug, Default)]
+pub struct References {
+    pub mentions: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+/// Collects mentions and tags from `content`, ignoring those
+/// that are inside code. Tags are case-insensitive and thus
+/// lowercased.
+pub fn extract(content: &str) -> References {
+    let mut references = References::default();
+    let mut in_code_block = false;
+    for event in Parser::new(content) {
+        let text = match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                in_code_block = true;
+                continue;
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                in_code_block = false;
+                continue;
+            }
+            Event::Text(text) if !in_code_block => text,
+            _ => continue,
+        };
+        for captures in MENTION_REGEX.captures_iter(&text) {
+            let name = &captures[1];
+            if !references.mentions.iter().any(|it| it == name) {
+                references.mentions.push(name.to_owned());
+            }
+        }
+        for captures in TAG_REGEX.captures_iter(&text) {
+            let tag = captures[1].to_lowercase();
+            if tag.chars().count() <= MAX_TAG_LENGTH && !references.tags.contains(&tag) {
+                references.tags.push(tag);
+            }
+        }
+    }
+    references
+}
+
+/// Replaces the mentions and tags of post `pid` with those found in `content`.
+/// Mentions of users that do not exist are ignored.
+pub async fn link_post(
+    transaction: &mut sqlx::Transaction<'_, Postgres>,
+    pid: i64,
+    content: &str,
+) -> Result<(), sqlx::Error> {
+    let References { mentions, tags } = extract(content);
+    sqlx::query!("DELETE FROM post_mentions WHERE post_id = $1", &pid)
+        .execute(&mut **transaction)
+        .await?;
+    sqlx::query!(
+        "INSERT INTO post_mentions (post_id, user_id)
+        SELECT $1, u.id FROM users u WHERE u.name = ANY($2)",
+        &pid,
+        &mentions,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!("DELETE FROM post_tags WHERE post_id = $1", &pid)
+        .execute(&mut **transaction)
+        .await?;
+    if tags.is_empty() {
+        return Ok(());
+    }
+    sqlx::query!(
+        "WITH inserted AS (
+            INSERT INTO tags (name) SELECT UNNEST($2::TEXT[])
+            ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+            RETURNING id
+        )
+        INSERT INTO post_tags (post_id, tag_id) SELECT $1, id FROM inserted",
+        &pid,
+        &tags,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Replaces the mentions and tags of comment `cid` with those found in `content`.
+/// Mentions of users that do not exist are ignored.
+pub async fn link_comment(
+    transaction: &mut sqlx::Transaction<'_, Postgres>,
+    cid: i64,
+    content: &str,
+) -> Result<(), sqlx::Error> {
+    let References { mentions, tags } = extract(content);
+    sqlx::query!("DELETE FROM comment_mentions WHERE comment_id = $1", &cid)
+        .execute(&mut **transaction)
+        .await?;
+    sqlx::query!(
+        "INSERT INTO comment_mentions (comment_id, user_id)
+        SELECT $1, u.id FROM users u WHERE u.name = ANY($2)",
+        &cid,
+        &mentions,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!("DELETE FROM comment_tags WHERE comment_id = $1", &cid)
+        .execute(&mut **transaction)
+        .await?;
+    if tags.is_empty() {
+        return Ok(());
+    }
+    sqlx::query!(
+        "WITH inserted AS (
+            INSERT INTO tags (name) SELECT UNNEST($2::TEXT[])
+            ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+            RETURNING id
+        )
+        INSERT INTO comment_tags (comment_id, tag_id) SELECT $1, id FROM inserted",
+        &cid,
+        &tags,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
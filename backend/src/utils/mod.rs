@@ -3,8 +3,12 @@ pub mod emails;
 pub mod errors;
 pub mod futures;
 pub mod image;
+pub mod isbn;
+pub mod markdown;
+pub mod mentions;
 pub mod os;
 pub mod password;
+pub mod processing;
 pub mod response;
 pub mod structs;
 pub mod uploads;
@@ -0,0 +1,219 @@
+use super::uploads::{FileVariant, UploadsError};
+use image::{
+    codecs::jpeg::JpegEncoder, error::DecodingError, imageops::FilterType, DynamicImage,
+    ImageDecoder, ImageError, ImageFormat, ImageReader, Limits,
+};
+use std::io::Cursor;
+
+/// The largest width or height we accept for an uploaded image.
+const MAX_DIMENSION: u32 = 10_000;
+
+/// The quality lossy originals and variants are encoded with.
+const QUALITY: u8 = 85;
+
+pub struct EncodedImage {
+    pub bytes: Vec<u8>,
+    pub ext: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct ProcessedImage {
+    pub original: EncodedImage,
+    pub variants: Vec<(FileVariant, EncodedImage)>,
+}
+
+impl ProcessedImage {
+    /// The number of bytes the original and its variants take up together.
+    pub fn size(&self) -> u64 {
+        self.variants
+            .iter()
+            .fold(self.original.bytes.len(), |size, (_, variant)| {
+                size + variant.bytes.len()
+            }) as u64
+    }
+}
+
+impl FileVariant {
+    pub const ALL: [FileVariant; 2] = [FileVariant::Thumbnail, FileVariant::Medium];
+
+    /// The size of the square the variant is resized to fit in.
+    pub fn max_size(&self) -> u32 {
+        match self {
+            FileVariant::Thumbnail => 200,
+            FileVariant::Medium => 800,
+        }
+    }
+}
+
+fn is_supported(format: ImageFormat) -> bool {
+    matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP
+    )
+}
+
+/// Detects the format of an image from its content, ignoring whatever
+/// extension the client has claimed.
+pub fn sniff(bytes: &[u8]) -> Result<ImageFormat, UploadsError> {
+    image::guess_format(bytes)
+        .ok()
+        .filter(|format| is_supported(*format))
+        .ok_or(UploadsError::UnsupportedFormat)
+}
+
+/// Returns the format a client has announced with a MIME type.
+pub fn from_mime_type(mime_type: &str) -> Result<ImageFormat, UploadsError> {
+    ImageFormat::from_mime_type(mime_type)
+        .filter(|format| is_supported(*format))
+        .ok_or(UploadsError::UnsupportedFormat)
+}
+
+/// Decodes an image, rotating it according to its EXIF orientation.
+pub fn decode(bytes: &[u8], format: ImageFormat) -> Result<DynamicImage, UploadsError> {
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Encodes `image` from scratch, which leaves out any metadata the
+/// original file carried.
+pub fn encode(image: &DynamicImage, format: ImageFormat) -> Result<EncodedImage, UploadsError> {
+    encode_with_quality(image, format, QUALITY)
+}
+
+/// Whether `format` is encoded lossily, and so depends on a quality.
+pub fn is_lossy(format: ImageFormat) -> bool {
+    matches!(format, ImageFormat::Jpeg | ImageFormat::WebP)
+}
+
+/// Same as [`encode`], with `quality` ranging from 1 to 100. Formats that
+/// are not lossy ignore it.
+pub fn encode_with_quality(
+    image: &DynamicImage,
+    format: ImageFormat,
+    quality: u8,
+) -> Result<EncodedImage, UploadsError> {
+    let mut bytes = Vec::new();
+    match format {
+        ImageFormat::Jpeg => {
+            let encoder = JpegEncoder::new_with_quality(&mut bytes, quality);
+            if image.color().has_alpha() {
+                DynamicImage::from(image.to_rgb8()).write_with_encoder(encoder)?;
+            } else {
+                image.write_with_encoder(encoder)?;
+            }
+        }
+        // `image` can only encode lossless WebP, which is several times
+        // larger than lossy WebP for photos.
+        ImageFormat::WebP => {
+            let encoded = if image.color().has_alpha() {
+                let pixels = image.to_rgba8();
+                webp::Encoder::from_rgba(&pixels, image.width(), image.height())
+                    .encode(f32::from(quality))
+            } else {
+                let pixels = image.to_rgb8();
+                webp::Encoder::from_rgb(&pixels, image.width(), image.height())
+                    .encode(f32::from(quality))
+            };
+            bytes.extend_from_slice(&encoded);
+        }
+        format => image.write_to(&mut Cursor::new(&mut bytes), format)?,
+    }
+    Ok(EncodedImage {
+        bytes,
+        ext: format.extensions_str()[0],
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+fn gif_error(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> UploadsError {
+    UploadsError::InvalidImage(ImageError::Decoding(DecodingError::new(
+        ImageFormat::Gif.into(),
+        err,
+    )))
+}
+
+/// Copies the frames of a GIF as they are, which leaves out its comment
+/// and application extensions, like XMP, except for how often it loops.
+fn strip_gif(bytes: &[u8]) -> Result<Vec<u8>, UploadsError> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(bytes).map_err(gif_error)?;
+    let mut stripped = Vec::new();
+    let mut encoder = gif::Encoder::new(
+        &mut stripped,
+        decoder.width(),
+        decoder.height(),
+        decoder.global_palette().unwrap_or_default(),
+    )
+    .map_err(gif_error)?;
+    // How often a GIF loops is only known once its extensions before the
+    // first frame are read, and it is played once when they do not say.
+    let first = decoder.read_next_frame().map_err(gif_error)?.cloned();
+    if decoder.repeat() != gif::Repeat::Finite(0) {
+        encoder.set_repeat(decoder.repeat()).map_err(gif_error)?;
+    }
+    if let Some(frame) = first {
+        encoder.write_frame(&frame).map_err(gif_error)?;
+    }
+    while let Some(frame) = decoder.read_next_frame().map_err(gif_error)? {
+        encoder.write_frame(frame).map_err(gif_error)?;
+    }
+    drop(encoder);
+    Ok(stripped)
+}
+
+/// Validates an uploaded image, strips its metadata and generates
+/// the variants that are smaller than the original.
+pub fn process(bytes: &[u8]) -> Result<ProcessedImage, UploadsError> {
+    let format = sniff(bytes)?;
+    let image = decode(bytes, format)?;
+    let original = match format {
+        // Re-encoding GIFs would only keep their first frame.
+        ImageFormat::Gif => EncodedImage {
+            bytes: strip_gif(bytes)?,
+            ext: "gif",
+            width: image.width(),
+            height: image.height(),
+        },
+        format => encode(&image, format)?,
+    };
+    let variant_format = match format {
+        ImageFormat::Gif => ImageFormat::Png,
+        format => format,
+    };
+    let mut variants = Vec::new();
+    for variant in FileVariant::ALL {
+        let size = variant.max_size();
+        if image.width() <= size && image.height() <= size {
+            continue;
+        }
+        let resized = image.resize(size, size, FilterType::Lanczos3);
+        variants.push((variant, encode(&resized, variant_format)?));
+    }
+    Ok(ProcessedImage { original, variants })
+}
+
+/// Resizes an image to fit within `width`, never enlarging it, and
+/// encodes it as `format`.
+pub fn transform(
+    bytes: &[u8],
+    width: Option<u32>,
+    format: ImageFormat,
+    quality: u8,
+) -> Result<EncodedImage, UploadsError> {
+    let mut image = decode(bytes, sniff(bytes)?)?;
+    if let Some(width) = width.filter(|width| *width < image.width()) {
+        image = image.resize(width, image.height(), FilterType::Lanczos3);
+    }
+    encode_with_quality(&image, format, quality)
+}
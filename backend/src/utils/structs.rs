@@ -9,13 +9,40 @@ use axum::{
 use axum_typed_multipart::{BaseMultipart, TryFromMultipart};
 use validator::Validate;
 
-use super::errors::AppError;
+use super::{errors::AppError, uploads::FileVariant};
 
-#[derive(Debug, sqlx::Type, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct AppImage {
     id: i64,
     ext: String,
     owner: i64,
+    /// The key the image is served under.
+    key: String,
+    width: Option<i32>,
+    height: Option<i32>,
+    /// Resized copies of the image, from the smallest to the largest.
+    #[serde(default)]
+    variants: Vec<AppImageVariant>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct AppImageVariant {
+    id: i64,
+    ext: String,
+    owner: i64,
+    key: String,
+    variant: FileVariant,
+    width: i32,
+    height: i32,
+}
+
+/// A part of a post's or a comment's content. Spoilers are
+/// kept apart so that clients can blur them.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentSpan {
+    Text { text: String },
+    Spoiler { text: String },
 }
 
 pub struct AppForm<T>(pub T);
@@ -109,3 +136,14 @@ where
         Ok(Self(base.data))
     }
 }
+
+/// Deserializes a field that can be left out, in which case it is `None`,
+/// or set to `null`, in which case it is `Some(None)`. Use along with
+/// `#[serde(default)]`.
+pub fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: serde::Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    <Option<T> as serde::Deserialize>::deserialize(deserializer).map(Some)
+}
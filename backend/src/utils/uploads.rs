@@ -1,18 +1,61 @@
 use crate::{
-    // hdfs::AppHdfs,
-    utils::{errors::AppError, validators::path_is_valid},
-};
-use aws_sdk_s3::{
-    error::SdkError,
-    operation::{
-        delete_object::DeleteObjectError, get_object::GetObjectError, put_object::PutObjectError,
+    scanner::{Scanner, ScannerError, Verdict},
+    settings::SETTINGS,
+    storage::{StorageBackend, StorageError},
+    utils::{
+        errors::AppError,
+        processing::{self, EncodedImage, ProcessedImage},
+        validators::path_is_valid,
     },
 };
 use axum::body::Bytes;
-use axum_typed_multipart::FieldData;
-// use libc::{O_CLOEXEC, O_CREAT, O_WRONLY};
+use axum_typed_multipart::{FieldData, TryFromField};
+use futures::TryStreamExt;
+use image::ImageFormat;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use sqlx::Postgres;
-use std::{ffi::OsStr, path::Path};
+use tracing::{event, Level};
+
+/// A resized copy of an uploaded image, stored as a child of the original.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, serde::Serialize, serde::Deserialize)]
+#[sqlx(type_name = "fvariant", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum FileVariant {
+    Thumbnail,
+    Medium,
+}
+
+/// Whether a file can be used. Files uploaded directly to storage are
+/// pending until the upload is completed, and files flagged by the
+/// scanner are quarantined for good.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, serde::Serialize, serde::Deserialize)]
+#[sqlx(type_name = "fstatus", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Pending,
+    Ready,
+    Quarantined,
+}
+
+/// Private files are only served to their owner, or with a signed URL.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    sqlx::Type,
+    serde::Serialize,
+    serde::Deserialize,
+    TryFromField,
+)]
+#[sqlx(type_name = "fvisibility", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+#[try_from_field(rename_all = "snake_case")]
+pub enum FileVisibility {
+    Public,
+    Private,
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum UploadsError {
@@ -20,12 +63,32 @@ pub enum UploadsError {
     InvalidName(String),
     #[error("received an IO error: {0}")]
     IoError(#[from] std::io::Error),
-    #[error("Error while deleting object: {0}")]
-    DeleteObjectError(#[from] SdkError<DeleteObjectError>),
-    #[error("Error while getting image: {0}")]
-    GetObjectError(#[from] SdkError<GetObjectError>),
-    #[error("Error while inserting image: {0}")]
-    PutObjectError(#[from] SdkError<PutObjectError>),
+    #[error("error while accessing storage: {0}")]
+    StorageError(#[from] StorageError),
+    #[error("error while scanning a file: {0}")]
+    ScannerError(#[from] ScannerError),
+    #[error("received a file that is not a supported image")]
+    UnsupportedFormat,
+    #[error("received an image that could not be processed: {0}")]
+    InvalidImage(#[from] image::ImageError),
+    #[error("received a file larger than {0} bytes")]
+    TooLarge(u64),
+    #[error("received a file of type {0}, which is not allowed")]
+    TypeNotAllowed(String),
+    #[error("storing {size} more bytes would exceed the quota of {quota} bytes")]
+    QuotaExceeded { size: u64, quota: u64 },
+    #[error("no file was received")]
+    MissingFile,
+    #[error("file {0} cannot be found")]
+    FileNotFound(i64),
+    #[error("file has not been uploaded yet")]
+    FileNotUploaded,
+    #[error("file {0} is still in use")]
+    FileInUse(i64),
+    #[error("file {0} is private")]
+    FileIsPrivate(i64),
+    #[error("file {0} was flagged as malicious")]
+    FileQuarantined(i64),
     #[error("this error is not expected")]
     Unexpected,
 }
@@ -38,78 +101,519 @@ fn validate_file_name<'a>(file_path: impl ToString) -> Result<String, UploadsErr
     Ok(file_path)
 }
 
+/// Rejects files larger than the configured limit.
+pub fn check_size(size: u64) -> Result<(), UploadsError> {
+    let limit = SETTINGS.storage.limits.size;
+    if size > limit {
+        return Err(UploadsError::TooLarge(limit));
+    }
+    Ok(())
+}
+
+/// Rejects images whose format is not one of the configured types.
+pub fn check_type(format: ImageFormat) -> Result<(), UploadsError> {
+    let mime_type = format.to_mime_type();
+    if !SETTINGS.storage.limits.types.iter().any(|t| t == mime_type) {
+        return Err(UploadsError::TypeNotAllowed(mime_type.to_owned()));
+    }
+    Ok(())
+}
+
+/// Returns how many bytes `user_id` stores, leaving out `except` and its variants.
+pub async fn usage<'c, E>(
+    executor: E,
+    user_id: i64,
+    except: Option<i64>,
+) -> Result<i64, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT COALESCE(SUM(size), 0)::BIGINT AS "used!"
+        FROM files
+        WHERE owner_id = $1 AND CASE
+            WHEN $2::BIGINT IS NULL THEN TRUE
+            ELSE id <> $2::BIGINT AND parent_id IS DISTINCT FROM $2::BIGINT
+        END"#,
+        &user_id,
+        &except as &_,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Rejects storing `size` more bytes if that would put `user_id` over quota.
+/// The user is locked until the transaction ends, so that concurrent
+/// uploads cannot both fit in the same remaining space.
+pub async fn check_quota<'c>(
+    transaction: &mut sqlx::Transaction<'c, Postgres>,
+    user_id: i64,
+    except: Option<i64>,
+    size: u64,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "SELECT id FROM users WHERE id = $1 FOR NO KEY UPDATE",
+        &user_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    let used = usage(&mut **transaction, user_id, except).await? as u64;
+    let quota = SETTINGS.storage.limits.quota;
+    if used + size > quota {
+        return Err(UploadsError::QuotaExceeded { size, quota }.into());
+    }
+    Ok(())
+}
+
+/// Generates the key a file is stored under. Keys are random so that
+/// private files cannot be found by guessing.
+pub fn new_key(ext: &str) -> String {
+    format!("{}.{}", uuid::Uuid::new_v4().simple(), ext)
+}
+
+/// The audience of signed URL tokens. They share the secret of session
+/// tokens, which have no audience and so are never taken for one another.
+const SIGNED_KEY_AUDIENCE: &str = "signed-url";
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SignedKeyClaims {
+    key: String,
+    aud: String,
+    exp: i64,
+}
+
+/// Returns the key a quarantined file is moved to.
+pub fn quarantine_key(key: &str) -> String {
+    format!("quarantine/{key}")
+}
+
+/// Creates a token that grants access to the file stored at `key` until `exp`.
+pub fn sign_key(key: &str, exp: i64) -> Result<String, AppError> {
+    let claims = SignedKeyClaims {
+        key: key.to_owned(),
+        aud: SIGNED_KEY_AUDIENCE.to_owned(),
+        exp,
+    };
+    Ok(encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(SETTINGS.secret.sec.as_bytes()),
+    )?)
+}
+
+/// Whether `token` was created by [`sign_key`] for `key` and has not expired.
+pub fn verify_key(token: &str, key: &str) -> bool {
+    let mut validation = Validation::default();
+    validation.set_audience(&[SIGNED_KEY_AUDIENCE]);
+    validation.set_required_spec_claims(&["exp", "aud"]);
+    decode::<SignedKeyClaims>(
+        token,
+        &DecodingKey::from_secret(SETTINGS.secret.sec.as_bytes()),
+        &validation,
+    )
+    .is_ok_and(|token| token.claims.key == key)
+}
+
+/// Inserts a row for `image` and returns its id and key.
+async fn insert_file<'c>(
+    transaction: &mut sqlx::Transaction<'c, Postgres>,
+    user_id: i64,
+    parent_id: Option<i64>,
+    variant: Option<FileVariant>,
+    visibility: FileVisibility,
+    image: &EncodedImage,
+) -> Result<(i64, String), sqlx::Error> {
+    let key = new_key(image.ext);
+    let width = image.width as i32;
+    let height = image.height as i32;
+    let size = image.bytes.len() as i64;
+    let fid = if let Some(pid) = parent_id {
+        sqlx::query_scalar!(
+            "INSERT INTO files (owner_id, parent_id, ext, path, variant, width, height, size, key, visibility)
+            VALUES ($1, $2, $3, (SELECT path || TEXT2LTREE(id::VARCHAR(255)) FROM files WHERE id = $2), $4, $5, $6, $7, $8, $9)
+            RETURNING id",
+            &user_id,
+            &pid,
+            image.ext,
+            variant as Option<FileVariant>,
+            &width,
+            &height,
+            &size,
+            &key,
+            visibility as FileVisibility,
+        )
+        .fetch_one(&mut **transaction)
+        .await?
+    } else {
+        sqlx::query_scalar!(
+            "INSERT INTO files (owner_id, ext, path, width, height, size, key, visibility)
+            VALUES ($1, $2, 'Top', $3, $4, $5, $6, $7)
+            RETURNING id",
+            &user_id,
+            image.ext,
+            &width,
+            &height,
+            &size,
+            &key,
+            visibility as FileVisibility,
+        )
+        .fetch_one(&mut **transaction)
+        .await?
+    };
+    Ok((fid, key))
+}
+
+/// Stores a processed image at `key`, creating rows for its variants.
+async fn store_image<'c>(
+    transaction: &mut sqlx::Transaction<'c, Postgres>,
+    storage: &dyn StorageBackend,
+    user_id: i64,
+    file_id: i64,
+    key: &str,
+    visibility: FileVisibility,
+    image: ProcessedImage,
+) -> Result<(), AppError> {
+    storage
+        .put(key, image.original.bytes.into())
+        .await
+        .map_err(UploadsError::from)?;
+
+    for (variant, image) in image.variants {
+        let (_, key) = insert_file(
+            transaction,
+            user_id,
+            Some(file_id),
+            Some(variant),
+            visibility,
+            &image,
+        )
+        .await?;
+        storage
+            .put(&key, image.bytes.into())
+            .await
+            .map_err(UploadsError::from)?;
+    }
+    Ok(())
+}
+
+/// Stores a file the scanner has flagged away from the others, and
+/// records it as quarantined so that it is never served.
+async fn quarantine<'c>(
+    transaction: &mut sqlx::Transaction<'c, Postgres>,
+    storage: &dyn StorageBackend,
+    user_id: i64,
+    visibility: FileVisibility,
+    ext: &str,
+    bytes: Bytes,
+    threat: String,
+) -> Result<i64, AppError> {
+    let size = bytes.len() as u64;
+    check_quota(transaction, user_id, None, size).await?;
+    let key = new_key(ext);
+    let fid = sqlx::query_scalar!(
+        "INSERT INTO files (owner_id, ext, path, status, size, key, visibility, threat)
+        VALUES ($1, $2, 'Top', 'quarantined', $3, $4, $5, $6)
+        RETURNING id",
+        &user_id,
+        ext,
+        size as i64,
+        &key,
+        visibility as FileVisibility,
+        &threat,
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    storage
+        .put(&quarantine_key(&key), bytes)
+        .await
+        .map_err(UploadsError::from)?;
+    event!(
+        Level::WARN,
+        file_id = fid,
+        user_id,
+        threat,
+        "quarantined an uploaded file"
+    );
+    Ok(fid)
+}
+
+/// Validates and stores an uploaded image together with its resized variants,
+/// and returns its id and status. The extension is taken from the content of
+/// the file, not from its name. Files flagged by the scanner are quarantined
+/// instead, so callers have to check the status of the file before using it.
 pub async fn upload_file<'c>(
     transaction: &mut sqlx::Transaction<'c, Postgres>,
-    s3: &aws_sdk_s3::Client,
-    // hdfs: &AppHdfs,
+    storage: &dyn StorageBackend,
+    scanner: &dyn Scanner,
     user_id: i64,
     parent_id: Option<i64>,
+    visibility: FileVisibility,
     file: FieldData<Bytes>,
-) -> Result<i64, AppError> {
-    let file_name = validate_file_name(
+) -> Result<(i64, FileStatus), AppError> {
+    validate_file_name(
         file.metadata
             .file_name
             .ok_or(UploadsError::InvalidName("None".to_owned()))?
             .as_str(),
     )?;
-    let ext = Path::new(&file_name)
-        .extension()
-        .and_then(OsStr::to_str)
-        .ok_or(UploadsError::InvalidName(file_name.clone()))?;
-    let uid = validate_file_name(user_id)?;
-
-    let fid = {
-        if let Some(pid) = parent_id {
-            sqlx::query_scalar!(
-                "INSERT INTO files (owner_id, parent_id, ext, path)
-                VALUES ($1, $2, $3, (SELECT path || TEXT2LTREE(id::VARCHAR(255)) FROM files WHERE id = $2))
-                RETURNING id",
-                &user_id,
-                &pid,
-                &ext,
-            )
-            .fetch_one(&mut **transaction)
-        } else {
-            sqlx::query_scalar!(
-                "INSERT INTO files (owner_id, ext, path) VALUES ($1, $2, 'Top') RETURNING id",
-                &user_id,
-                &ext,
-            )
-            .fetch_one(&mut **transaction)
-        }
+    check_size(file.contents.len() as u64)?;
+    let format = processing::sniff(&file.contents)?;
+    check_type(format)?;
+    if let Verdict::Infected(threat) = scanner
+        .scan(&file.contents)
+        .await
+        .map_err(UploadsError::from)?
+    {
+        let ext = format.extensions_str()[0];
+        let fid = quarantine(
+            transaction,
+            storage,
+            user_id,
+            visibility,
+            ext,
+            file.contents,
+            threat,
+        )
+        .await?;
+        return Ok((fid, FileStatus::Quarantined));
+    }
+
+    let image = tokio::task::spawn_blocking(move || processing::process(&file.contents))
+        .await
+        .map_err(|_| UploadsError::Unexpected)??;
+    check_quota(transaction, user_id, None, image.size()).await?;
+
+    let (fid, key) = insert_file(
+        transaction,
+        user_id,
+        parent_id,
+        None,
+        visibility,
+        &image.original,
+    )
+    .await?;
+    store_image(transaction, storage, user_id, fid, &key, visibility, image).await?;
+
+    Ok((fid, FileStatus::Ready))
+}
+
+/// Processes a file that was uploaded directly to storage the same way
+/// [`upload_file`] does, and returns whether it is ready to be referenced
+/// or was quarantined.
+pub async fn complete_upload<'c>(
+    transaction: &mut sqlx::Transaction<'c, Postgres>,
+    storage: &dyn StorageBackend,
+    scanner: &dyn Scanner,
+    user_id: i64,
+    file_id: i64,
+) -> Result<FileStatus, AppError> {
+    let file = sqlx::query!(
+        r#"SELECT
+            ext,
+            key,
+            visibility AS "visibility: FileVisibility",
+            status AS "status: FileStatus"
+        FROM files
+        WHERE id = $1 AND owner_id = $2 AND parent_id IS NULL
+        FOR UPDATE"#,
+        &file_id,
+        &user_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or(UploadsError::FileNotFound(file_id))?;
+    if file.status != FileStatus::Pending {
+        return Ok(file.status);
+    }
+
+    let key = file.key;
+    let object = match storage.get(&key).await {
+        Err(StorageError::NotFound(_)) => return Err(UploadsError::FileNotUploaded.into()),
+        object => object.map_err(UploadsError::from)?,
+    };
+    if let Err(err) = check_size(object.metadata.size) {
+        storage.delete(&key).await.map_err(UploadsError::from)?;
+        return Err(err.into());
     }
+    let chunks: Vec<Bytes> = object
+        .body
+        .try_collect()
+        .await
+        .map_err(UploadsError::from)?;
+    let bytes = Bytes::from(chunks.concat());
+
+    if let Verdict::Infected(threat) = scanner.scan(&bytes).await.map_err(UploadsError::from)? {
+        sqlx::query!(
+            "UPDATE files SET status = 'quarantined', size = $2, threat = $3 WHERE id = $1",
+            &file_id,
+            bytes.len() as i64,
+            &threat,
+        )
+        .execute(&mut **transaction)
+        .await?;
+        storage
+            .put(&quarantine_key(&key), bytes)
+            .await
+            .map_err(UploadsError::from)?;
+        storage.delete(&key).await.map_err(UploadsError::from)?;
+        event!(
+            Level::WARN,
+            file_id,
+            user_id,
+            threat,
+            "quarantined an uploaded file"
+        );
+        return Ok(FileStatus::Quarantined);
+    }
+
+    let image = tokio::task::spawn_blocking(move || processing::process(&bytes))
+        .await
+        .map_err(|_| UploadsError::Unexpected)?;
+    // The content has to match the type the client announced, since
+    // the extension was picked from it.
+    let image = match image {
+        Ok(image) if image.original.ext == file.ext => image,
+        result => {
+            storage.delete(&key).await.map_err(UploadsError::from)?;
+            return Err(result
+                .err()
+                .unwrap_or(UploadsError::UnsupportedFormat)
+                .into());
+        }
+    };
+    // The object is kept, so that the upload can be completed once
+    // enough space has been freed.
+    check_quota(transaction, user_id, Some(file_id), image.size()).await?;
+
+    sqlx::query!(
+        "UPDATE files SET status = 'ready', width = $2, height = $3, size = $4 WHERE id = $1",
+        &file_id,
+        image.original.width as i32,
+        image.original.height as i32,
+        image.original.bytes.len() as i64,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    store_image(
+        transaction,
+        storage,
+        user_id,
+        file_id,
+        &key,
+        file.visibility,
+        image,
+    )
     .await?;
+    Ok(FileStatus::Ready)
+}
 
-    let path = format!("{}-{}.{}", uid, fid, ext);
-
-    async {
-        // let dir = path.parent().ok_or(UploadsError::Unexpected)?;
-
-        // hdfs.mkdir(dir.to_str().ok_or(UploadsError::Unexpected)?)?;
-        //
-        // TODO: Implement AsyncWrite for File.
-        // let mut file_stream = std::io::BufWriter::new(
-        // hdfs.open(
-        // path.as_path().to_str().unwrap(),
-        // O_CLOEXEC | O_WRONLY | O_CREAT,
-        // None,
-        // None,
-        // )
-        // .map_err(|_| UploadsError::Unexpected)?,
-        // );
-        //
-        // std::io::copy(&mut file.contents.as_ref(), &mut file_stream)?;
-
-        s3.put_object()
-            .bucket("blisk-s3")
-            .key(path)
-            .body(file.contents.into())
-            .send()
+/// Returns the id of `file` once uploaded, or `file_id` if it points to
+/// an image `user_id` has already uploaded.
+pub async fn upload_or_reference<'c>(
+    transaction: &mut sqlx::Transaction<'c, Postgres>,
+    storage: &dyn StorageBackend,
+    scanner: &dyn Scanner,
+    user_id: i64,
+    file: Option<FieldData<Bytes>>,
+    file_id: Option<i64>,
+) -> Result<i64, AppError> {
+    let file_id = match (file, file_id) {
+        (Some(file), _) => {
+            let (file_id, _) = upload_file(
+                transaction,
+                storage,
+                scanner,
+                user_id,
+                None,
+                FileVisibility::Public,
+                file,
+            )
             .await?;
+            file_id
+        }
+        (None, Some(file_id)) => file_id,
+        (None, None) => return Err(UploadsError::MissingFile.into()),
+    };
+    let file = sqlx::query!(
+        r#"SELECT
+            status AS "status: FileStatus",
+            visibility AS "visibility: FileVisibility"
+        FROM files
+        WHERE id = $1 AND owner_id = $2 AND parent_id IS NULL"#,
+        &file_id,
+        &user_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or(UploadsError::FileNotFound(file_id))?;
+    match file.status {
+        FileStatus::Ready => {}
+        FileStatus::Pending => return Err(UploadsError::FileNotUploaded.into()),
+        FileStatus::Quarantined => return Err(UploadsError::FileQuarantined(file_id).into()),
+    }
+    // Books and profiles are shown to everyone.
+    if file.visibility == FileVisibility::Private {
+        return Err(UploadsError::FileIsPrivate(file_id).into());
+    }
+    Ok(file_id)
+}
 
-        Ok::<_, UploadsError>(())
+/// Deletes a file owned by `user_id` along with its variants, and returns
+/// their keys. Files still used by a book or a profile are kept. The objects
+/// must only be removed with [`delete_objects`] once the transaction is
+/// committed, since a rollback would leave rows without them.
+pub async fn delete_file<'c>(
+    transaction: &mut sqlx::Transaction<'c, Postgres>,
+    user_id: i64,
+    file_id: i64,
+) -> Result<Vec<String>, AppError> {
+    let is_referenced = sqlx::query_scalar!(
+        r#"SELECT (
+            EXISTS (SELECT 1 FROM books WHERE cover_id = f.id OR spine_id = f.id)
+            OR EXISTS (SELECT 1 FROM users WHERE picture_id = f.id)
+        ) AS "is_referenced!"
+        FROM files f
+        WHERE f.id = $1 AND f.owner_id = $2 AND f.variant IS NULL
+        FOR UPDATE"#,
+        &file_id,
+        &user_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or(UploadsError::FileNotFound(file_id))?;
+    if is_referenced {
+        return Err(UploadsError::FileInUse(file_id).into());
     }
+
+    // Variants are removed along with the file by `ON DELETE CASCADE`.
+    let keys = sqlx::query_scalar!(
+        "SELECT key FROM files WHERE id = $1 OR parent_id = $1",
+        &file_id,
+    )
+    .fetch_all(&mut **transaction)
     .await?;
+    sqlx::query!("DELETE FROM files WHERE id = $1", &file_id)
+        .execute(&mut **transaction)
+        .await?;
+    Ok(keys)
+}
 
-    Ok(fid)
+/// Removes the objects of deleted files, along with every transformed and
+/// quarantined copy of them.
+pub async fn delete_objects(storage: &dyn StorageBackend, keys: &[String]) -> Result<(), AppError> {
+    for key in keys {
+        for cached in storage
+            .list(&format!("cache/{key}/"))
+            .await
+            .map_err(UploadsError::from)?
+        {
+            storage.delete(&cached).await.map_err(UploadsError::from)?;
+        }
+        storage
+            .delete(&quarantine_key(key))
+            .await
+            .map_err(UploadsError::from)?;
+        storage.delete(key).await.map_err(UploadsError::from)?;
+    }
+    Ok(())
 }
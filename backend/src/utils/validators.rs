@@ -1,3 +1,6 @@
+use crate::{settings::SETTINGS, utils::isbn};
+use validator::ValidationError;
+
 pub fn path_is_valid(path: &str) -> bool {
     let path = std::path::Path::new(path);
     let mut components = path.components().peekable();
@@ -10,3 +13,36 @@ pub fn path_is_valid(path: &str) -> bool {
 
     components.count() == 1
 }
+
+pub fn width_is_allowed(width: u32) -> Result<(), ValidationError> {
+    if SETTINGS.images.widths.contains(&width) {
+        return Ok(());
+    }
+    Err(ValidationError::new("width").with_message(
+        format!("Width must be one of {:?}!", SETTINGS.images.widths).into(),
+    ))
+}
+
+pub fn quality_is_allowed(quality: u8) -> Result<(), ValidationError> {
+    if SETTINGS.images.qualities.contains(&quality) {
+        return Ok(());
+    }
+    Err(ValidationError::new("quality").with_message(
+        format!("Quality must be one of {:?}!", SETTINGS.images.qualities).into(),
+    ))
+}
+
+pub fn rating_is_valid(rating: f32) -> Result<(), ValidationError> {
+    if (1.0..=5.0).contains(&rating) && (rating * 2.0).fract() == 0.0 {
+        return Ok(());
+    }
+    Err(ValidationError::new("rating")
+        .with_message("Rating must be between 1 and 5 stars, in half stars!".into()))
+}
+
+pub fn isbn_is_valid(value: &str) -> Result<(), ValidationError> {
+    if isbn::normalize(value).is_some() {
+        return Ok(());
+    }
+    Err(ValidationError::new("isbn").with_message("ISBN is not valid!".into()))
+}
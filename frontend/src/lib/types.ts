@@ -24,7 +24,10 @@ export interface User {
 
 export type BookReaction = "like" | "dislike";
 
-export type BookReactionMetadata = Record<"total" | BookReaction, number>;
+export type BookReactionMetadata = Record<"total" | BookReaction | "rated", number> & {
+  average: number | null;
+  histogram: number[];
+};
 
 export type ReactionFor = (typeof VALID_REACTION_FOR)[number];
 
@@ -44,6 +47,7 @@ export interface Post {
   book_cover: Image | null;
   book_spine: Image | null;
   book_reaction: BookReaction;
+  rating: number | null;
   reactions: ReactionMetadata | null;
   user_reaction: ReactionType | null;
   comments: Comment[] | null;
@@ -90,10 +94,56 @@ export interface Book {
   reviews: Post[];
 }
 
+export interface BookFacetCount {
+  count: number;
+}
+
+export interface BookRangeFacet<T> extends BookFacetCount {
+  min: T;
+  max: T | null;
+}
+
+export interface BookFacets {
+  languages: (BookFacetCount & { code: string; name: string })[];
+  categories: (BookFacetCount & { id: number; name: string })[];
+  authors: (BookFacetCount & { id: number; name: string })[];
+  pages: BookRangeFacet<number>[];
+  ratings: BookRangeFacet<number>[];
+  reviews: { with: number; without: number };
+}
+
+export interface BooksResponse<B = Book> {
+  books: B[];
+  facets: BookFacets;
+}
+
 export interface BookCategoryWithBooks extends BookCategory {
   books: Pick<Book, "title" | "name" | "cover_image" | "spine_image">[];
 }
 
+export type BookFormat = "hardcover" | "paperback" | "ebook" | "audiobook";
+
+export interface BookEdition {
+  id: number;
+  isbn: string;
+  publisher: string | null;
+  published_on: string | null;
+  format: BookFormat;
+  pages: number | null;
+}
+
+export interface BookSeries {
+  id: number;
+  name: string;
+  position: number;
+  next: { title: string; name: string; position: number } | null;
+}
+
+export interface BookWithEditions extends Book {
+  editions: BookEdition[];
+  series: BookSeries | null;
+}
+
 export interface ReadingTracker {
   book_title: string;
   book_cover: Image | null;
@@ -102,6 +152,7 @@ export interface ReadingTracker {
   ends_at: string;
   pages_read: number;
   completed: boolean;
+  edition: BookEdition | null;
 }
 
 export type SetHeaders = (headers: Record<string, string>) => void;
@@ -122,6 +173,7 @@ export interface Image {
   id: number;
   ext: string;
   owner: number;
+  key: string;
 }
 
 export type BookRating =
@@ -1,7 +1,7 @@
 import { fetchBackend } from "$lib/backend";
 import { error } from "@sveltejs/kit";
 import type { PageServerLoad } from "./$types";
-import type { Book } from "$lib/types";
+import type { BooksResponse } from "$lib/types";
 
 export const load: PageServerLoad = async ({ cookies, fetch, setHeaders, url }) => {
   const q = url.searchParams.get("q");
@@ -15,7 +15,7 @@ export const load: PageServerLoad = async ({ cookies, fetch, setHeaders, url })
     .map((author) => `&categories=${author}`)
     .join("");
   const fetchUrl: `/${string}` = `/books?include_reviews=false${query}${authors}${categories}`;
-  const res = await fetchBackend<Book[]>(fetchUrl, {
+  const res = await fetchBackend<BooksResponse>(fetchUrl, {
     authz: "optional",
     cookies,
     fetch,
@@ -24,5 +24,5 @@ export const load: PageServerLoad = async ({ cookies, fetch, setHeaders, url })
   if (!res.ok) {
     error(res.status, res.error);
   }
-  return { title: q ? `${q} - Books search` : "Books", books: res.data };
+  return { title: q ? `${q} - Books search` : "Books", books: res.data.books, facets: res.data.facets };
 };
@@ -1,7 +1,7 @@
 import { createReaction, fetchBackend } from "$lib/backend";
 import { error } from "@sveltejs/kit";
 import type { Actions, PageServerLoad } from "./$types";
-import type { Book } from "$lib/types";
+import type { BookWithEditions } from "$lib/types";
 
 export const actions: Actions = {
   async react({ cookies, fetch, request, setHeaders }) {
@@ -10,7 +10,7 @@ export const actions: Actions = {
 };
 
 export const load: PageServerLoad = async ({ cookies, params, setHeaders }) => {
-  const res = await fetchBackend<Book>(`/books/${params.id}`, {
+  const res = await fetchBackend<BookWithEditions>(`/books/${params.id}`, {
     authz: "optional",
     cookies,
     fetch,
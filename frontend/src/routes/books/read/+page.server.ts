@@ -3,7 +3,7 @@ import { z } from "zod";
 import { error, fail, redirect } from "@sveltejs/kit";
 import { convertFormData } from "$lib/utils";
 import { fetchBackend } from "$lib/backend";
-import type { Book } from "$lib/types";
+import type { Book, BooksResponse } from "$lib/types";
 import { base } from "$app/paths";
 
 const readSchema = z
@@ -61,7 +61,7 @@ export const actions = {
 };
 
 export const load = async ({ cookies, fetch, setHeaders }) => {
-  const books = await fetchBackend<Omit<Book, "reviews">[]>("/books?include_reviews=false", {
+  const books = await fetchBackend<BooksResponse<Omit<Book, "reviews">>>("/books?include_reviews=false", {
     authz: false,
     cookies,
     fetch,
@@ -73,7 +73,7 @@ export const load = async ({ cookies, fetch, setHeaders }) => {
   const now = dayjs();
   return {
     title: "Read",
-    books: books.data,
+    books: books.data.books,
     now: now.format("YYYY-MM-DD"),
     oneWeekLater: now.add(1, "week").format("YYYY-MM-DD"),
     oneMonthLater: now.add(1, "month").format("YYYY-MM-DD"),